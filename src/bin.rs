@@ -1,28 +1,41 @@
 use anyhow::{Context, Result};
 mod config;
-use ateam::{cli, followup_render, pr_render};
+use ateam::{cli, followup_render, pr_render, tui};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    config::load_env();
+
     let cmd = cli::command();
 
     let config = config::get_config().context("while reading from environment")?;
 
     match cmd {
         cli::Ateam {
-            cmd: cli::Command::Pr(pr),
+            cmd: cli::Command::Pr(mut pr),
         } => {
-            println!("{}", pr_render(&pr, &config.github_api_token).await?);
+            config.apply(&mut pr);
+            print(&pr_render(&pr, &config.github_api_token).await?);
             Ok(())
         }
         cli::Ateam {
             cmd: cli::Command::Followup(followup),
         } => {
-            println!(
-                "{}",
-                followup_render(&followup, &config.github_api_token).await
-            );
+            print(&followup_render(&followup, &config.github_api_token).await?);
             Ok(())
         }
+        cli::Ateam {
+            cmd: cli::Command::Tui(mut pr),
+        } => {
+            config.apply(&mut pr);
+            tui(&pr, &config.github_api_token).await
+        }
+    }
+}
+
+/// The streamed outputs are already printed
+fn print(output: &str) {
+    if !output.is_empty() {
+        println!("{output}");
     }
 }
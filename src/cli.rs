@@ -1,4 +1,9 @@
-use clap::{Args, Parser, Subcommand};
+use super::expr::Expr;
+use super::template::Template;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::str::FromStr;
 
 #[derive(Parser, Debug)]
 #[command(version, propagate_version = true)]
@@ -14,9 +19,11 @@ pub enum Command {
     Pr(PrArgs),
     /// It gives you some information about the reviews you already submitted and need your attention.
     Followup(FollowupArgs),
+    /// An interactive view of the ranked pull requests and of the followup reviews. It takes the options of `pr`.
+    Tui(PrArgs),
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct PrArgs {
     /// Repositiy. Can be used multiple times to select more than one
@@ -28,6 +35,9 @@ pub struct PrArgs {
     /// GitHub query. Can be used multiple times
     #[arg(long, short)]
     pub query: Vec<String>,
+    /// GitHub query run as a separate search. Can be used multiple times to select the pull requests matching any of them
+    #[arg(long)]
+    pub any_query: Vec<String>,
     /// Number of pull requests to display
     #[arg(long, short)]
     pub num: Option<usize>,
@@ -37,6 +47,33 @@ pub struct PrArgs {
     /// Output in JSON
     #[arg(long)]
     pub json: bool,
+    /// Output format. Overrides --short and --json
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+    /// Output every item with this template, or with the template in the file @PATH. Overrides --format
+    #[arg(long)]
+    pub template: Option<Template>,
+    /// Columns of the table, in order. A column can have a maximum width, like pr:60
+    #[arg(
+        long,
+        env = "ATEAM_COLUMNS",
+        value_name = "COLUMN[:WIDTH]",
+        value_delimiter = ',',
+        default_values = DEFAULT_COLUMNS
+    )]
+    pub columns: Vec<ColumnSpec>,
+    /// Color the table and the short output: labels, CI, age and score
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = When::Auto)]
+    pub color: When,
+    /// Make the titles clickable links to the pull requests and hide the URLs, in the terminals supporting them
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = When::Auto)]
+    pub hyperlinks: When,
+    /// Omit the header row of the csv and tsv outputs
+    #[arg(long)]
+    pub no_header: bool,
+    /// Add a summary header (query, user, generation time and counts) to the markdown and html outputs
+    #[arg(long)]
+    pub summary: bool,
     /// Filter by label. Can be used multiple times
     #[arg(long)]
     pub label: Vec<String>,
@@ -49,6 +86,38 @@ pub struct PrArgs {
     /// Regexp filter on titles to exclude pull requests
     #[arg(long)]
     pub regex_not: Option<String>,
+    /// Filter expression on the pull requests, like 'ci != "fail" && additions < 400 && (requested || codeowner)'
+    #[arg(long = "where", value_name = "EXPR")]
+    pub where_expr: Option<Expr>,
+    /// Select pull requests with at most this number of changed lines (additions + deletions)
+    #[arg(long, value_name = "LINES")]
+    pub max_lines: Option<i64>,
+    /// Select pull requests with at least this number of changed lines (additions + deletions)
+    #[arg(long, value_name = "LINES")]
+    pub min_lines: Option<i64>,
+    /// Select pull requests with at most this number of changed files
+    #[arg(long, value_name = "FILES")]
+    pub max_files: Option<i64>,
+    /// Select pull requests with the last commit older than a duration (30m, 6h, 2d, 3w).
+    /// The creation of the pull request is used when the date of the last commit is unknown
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub older_than: Option<Duration>,
+    /// Select pull requests with the last commit newer than a duration (30m, 6h, 2d, 3w).
+    /// The creation of the pull request is used when the date of the last commit is unknown
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub newer_than: Option<Duration>,
+    /// Maximum number of pull requests of the same repository
+    #[arg(long, value_name = "NUM")]
+    pub max_per_repo: Option<usize>,
+    /// Maximum number of pull requests of the same author
+    #[arg(long, value_name = "NUM")]
+    pub max_per_author: Option<usize>,
+    /// Interleave the pull requests of the repositories or of the authors, taking the best of each in turn
+    #[arg(long, value_enum, value_name = "BY")]
+    pub round_robin: Option<RoundRobin>,
+    /// Group the table, markdown and json outputs, keeping the ranking inside each group
+    #[arg(long, value_enum, value_name = "BY")]
+    pub group_by: Option<GroupBy>,
     /// Include pull requests I have reviewed
     #[arg(long)]
     pub include_reviewed_by_me: bool,
@@ -61,6 +130,51 @@ pub struct PrArgs {
     /// Select pull requests I have been requested to review, explicitly or as a code owner
     #[arg(long)]
     pub requested: bool,
+    /// Select pull requests involving me: author, assignee, mentioned or commenter
+    #[arg(long)]
+    pub involves_me: bool,
+    /// Select pull requests mentioning me
+    #[arg(long)]
+    pub mentions_me: bool,
+    /// Select pull requests I have commented
+    #[arg(long)]
+    pub commented_by_me: bool,
+    /// Select pull requests updated since a date (2024-01-31) or a duration ago (30m, 6h, 2d, 3w)
+    #[arg(long, value_name = "DURATION|DATE")]
+    pub updated_since: Option<PointInTime>,
+    /// Select pull requests created before a date (2024-01-31) or a duration ago (30m, 6h, 2d, 3w)
+    #[arg(long, value_name = "DURATION|DATE")]
+    pub created_before: Option<PointInTime>,
+    /// Select pull requests to merge into this branch
+    #[arg(long, value_name = "BRANCH")]
+    pub base: Option<String>,
+    /// Include pull requests with conflicts
+    #[arg(long)]
+    pub include_conflicts: bool,
+    /// Select pull requests requested to one of my teams (team slug)
+    #[arg(long)]
+    pub team: Option<String>,
+    /// Select pull requests by this author. Can be used multiple times
+    #[arg(long)]
+    pub author: Vec<String>,
+    /// Exclude pull requests by this author. Can be used multiple times
+    #[arg(long)]
+    pub exclude_author: Vec<String>,
+    /// Select pull requests by the members of a team (ORG/SLUG, or the SLUG of one of my teams or of --org)
+    #[arg(long, value_name = "TEAM")]
+    pub author_team: Option<String>,
+    /// Exclude pull requests by bots
+    #[arg(long)]
+    pub exclude_bots: bool,
+    /// Login of a bot account, for --exclude-bots. Can be used multiple times
+    #[arg(
+        long = "bot",
+        value_name = "LOGIN",
+        env = "ATEAM_BOTS",
+        value_delimiter = ',',
+        default_values = ["dependabot", "renovate", "github-actions"]
+    )]
+    pub bots: Vec<String>,
     /// Include draft pull requests
     #[arg(long)]
     pub include_drafts: bool,
@@ -70,6 +184,9 @@ pub struct PrArgs {
     /// Include pull requests with tests failure
     #[arg(long)]
     pub include_tests_failure: bool,
+    /// Include pull requests with required checks not reported yet
+    #[arg(long)]
+    pub include_tests_missing: bool,
     /// Exclude pull requests without tests
     #[arg(long)]
     pub exclude_tests_none: bool,
@@ -79,9 +196,15 @@ pub struct PrArgs {
     /// Select tests via regexp. The others are ignored
     #[arg(long)]
     pub tests_regex: Option<String>,
-    /// Number of required approvals
+    /// Use all the checks for the tests state, not only the ones required by the base branch
+    #[arg(long)]
+    pub all_checks: bool,
+    /// Number of required approvals, when not defined by the base branch protection rules or rulesets
     #[arg(long, default_value = "2")]
     pub required_approvals: u8,
+    /// Number of required approvals of a repository (OWNER/REPO=NUM), when not defined by the base branch protection rules or rulesets. Can be used multiple times. It takes precedence over ATEAM_REPO_APPROVALS
+    #[arg(long, value_delimiter = ',')]
+    pub repo_approvals: Vec<RepoApprovals>,
     /// Look if I changed the same files in the past (slower)
     #[arg(long)]
     pub blame: bool,
@@ -101,12 +224,426 @@ pub struct FollowupArgs {
     /// Output in JSON
     #[arg(long)]
     pub json: bool,
+    /// Output format. Overrides --json
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+    /// Make the titles clickable links to the reviews and hide the URLs, in the terminals supporting them
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = When::Auto)]
+    pub hyperlinks: When,
+    /// Output every item with this template, or with the template in the file @PATH. Overrides --format
+    #[arg(long)]
+    pub template: Option<Template>,
+    /// Omit the header row of the csv and tsv outputs
+    #[arg(long)]
+    pub no_header: bool,
+    /// Add a summary header (query, user, generation time and counts) to the markdown and html outputs
+    #[arg(long)]
+    pub summary: bool,
     /// Query for another user
     #[arg(long)]
     pub user: Option<String>,
+    /// Add debug information
+    #[arg(long, short)]
+    pub debug: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    Table,
+    Short,
+    Json,
+    Markdown,
+    Csv,
+    Tsv,
+    Html,
+    Ndjson,
+}
+
+impl PrArgs {
+    #[must_use]
+    pub fn format(&self) -> Format {
+        match self.format {
+            Some(format) => format,
+            None if self.json => Format::Json,
+            None if self.short => Format::Short,
+            None => Format::Table,
+        }
+    }
+}
+
+impl FollowupArgs {
+    #[must_use]
+    pub fn format(&self) -> Format {
+        match self.format {
+            Some(format) => format,
+            None if self.json => Format::Json,
+            None => Format::Table,
+        }
+    }
+}
+
+pub const DEFAULT_COLUMNS: [&str; 13] = [
+    "pr",
+    "age",
+    "ci",
+    "conversations",
+    "approvals",
+    "diff",
+    "on-main",
+    "merge",
+    "blame",
+    "requested",
+    "codeowner",
+    "team",
+    "score",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Column {
+    /// URL, title, labels and failed checks
+    Pr,
+    Title,
+    Url,
+    Repo,
+    Author,
+    Labels,
+    Age,
+    Ci,
+    /// The state of all the checks, not only the required ones
+    AllCi,
+    Conversations,
+    Approvals,
+    Diff,
+    ChangedFiles,
+    /// The changed files, with --blame
+    Files,
+    OnMain,
+    Merge,
+    Blame,
+    Requested,
+    Codeowner,
+    Team,
+    Score,
+    ScoreAge,
+    ScoreCi,
+    ScoreConversations,
+    ScoreApprovals,
+    ScoreReviewers,
+    ScoreAdditions,
+    ScoreDeletions,
+    ScoreOnMain,
+    ScoreMerge,
+    ScoreBlame,
+    ScoreRequested,
+    ScoreCodeowner,
+}
+
+/// A column of the table with an optional maximum width: `pr:60`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColumnSpec {
+    pub column: Column,
+    pub width: Option<u16>,
+}
+
+impl FromStr for ColumnSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, width) = match s.split_once(':') {
+            Some((name, width)) => (
+                name,
+                Some(
+                    width
+                        .parse()
+                        .map_err(|_| format!("Invalid width {width} of the column {name}"))?,
+                ),
+            ),
+            None => (s, None),
+        };
+        let column = Column::from_str(name.trim(), true).map_err(|_| {
+            let names: Vec<String> = Column::value_variants()
+                .iter()
+                .filter_map(|column| column.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect();
+            format!(
+                "Unknown column {name}. Possible columns: {}",
+                names.join(", ")
+            )
+        })?;
+        Ok(ColumnSpec { column, width })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum When {
+    /// If the terminal supports it
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum GroupBy {
+    Repo,
+    Label,
+    Author,
+    Team,
+    /// What the pull requests need: review requested, code owner, waiting for CI, blocked on the author...
+    Attention,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum RoundRobin {
+    Repo,
+    Author,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct RepoApprovals {
+    pub repo: String,
+    pub approvals: u8,
+}
+
+impl TryFrom<String> for RepoApprovals {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl FromStr for RepoApprovals {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (repo, approvals) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected OWNER/REPO=NUM, found {s:?}"))?;
+        if !repo.contains('/') {
+            return Err(format!("expected OWNER/REPO, found {repo:?}"));
+        }
+        let approvals = approvals
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid number of approvals {approvals:?}: {e}"))?;
+        Ok(RepoApprovals {
+            repo: repo.trim().to_string(),
+            approvals,
+        })
+    }
+}
+
+/// A point in time, given as a date or as a duration before now
+#[derive(Clone, Debug, PartialEq)]
+pub enum PointInTime {
+    Date(NaiveDate),
+    Ago(Duration),
+}
+
+impl PointInTime {
+    #[must_use]
+    pub fn resolve(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            PointInTime::Date(date) => date.and_time(chrono::NaiveTime::MIN).and_utc(),
+            // Far in the past, the oldest representable time
+            PointInTime::Ago(duration) => now
+                .checked_sub_signed(*duration)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+        }
+    }
+}
+
+impl FromStr for PointInTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d") {
+            Ok(date) => Ok(PointInTime::Date(date)),
+            Err(_) => parse_duration(s).map(PointInTime::Ago).map_err(|_| {
+                format!("expected a date (2024-01-31) or a duration (2d), found {s:?}")
+            }),
+        }
+    }
+}
+
+/// Parse a duration like 30m, 6h, 2d or 3w
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let unit_at = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing the unit (m, h, d or w) in {s:?}"))?;
+    let (amount, unit) = s.split_at(unit_at);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|e| format!("invalid duration {s:?}: {e}"))?;
+    let duration = match unit {
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => return Err(format!("invalid unit {unit:?} in {s:?}: use m, h, d or w")),
+    };
+    // The duration is subtracted from the current time
+    duration
+        .filter(|duration| Utc::now().checked_sub_signed(*duration).is_some())
+        .ok_or_else(|| format!("duration too large {s:?}"))
 }
 
 #[must_use]
 pub fn command() -> Ateam {
     Ateam::parse()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_approvals_from_str() {
+        assert_eq!(
+            "frisoft/ateam=1".parse::<RepoApprovals>(),
+            Ok(RepoApprovals {
+                repo: "frisoft/ateam".to_string(),
+                approvals: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_repo_approvals_from_str_invalid() {
+        assert!("frisoft/ateam".parse::<RepoApprovals>().is_err());
+        assert!("ateam=1".parse::<RepoApprovals>().is_err());
+        assert!("frisoft/ateam=many".parse::<RepoApprovals>().is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let format = |args: &[&str]| match Ateam::parse_from(args).cmd {
+            Command::Pr(options) | Command::Tui(options) => options.format(),
+            Command::Followup(options) => options.format(),
+        };
+        assert_eq!(format(&["ateam", "pr"]), Format::Table);
+        assert_eq!(format(&["ateam", "pr", "--short"]), Format::Short);
+        assert_eq!(format(&["ateam", "pr", "--json", "--short"]), Format::Json);
+        assert_eq!(
+            format(&["ateam", "pr", "--json", "--format", "markdown"]),
+            Format::Markdown
+        );
+        assert_eq!(format(&["ateam", "followup", "--json"]), Format::Json);
+        assert_eq!(
+            format(&["ateam", "followup", "--format", "markdown"]),
+            Format::Markdown
+        );
+    }
+
+    #[test]
+    fn test_column_spec() {
+        assert_eq!(
+            "pr:60".parse(),
+            Ok(ColumnSpec {
+                column: Column::Pr,
+                width: Some(60)
+            })
+        );
+        assert_eq!(
+            "Score-Age".parse(),
+            Ok(ColumnSpec {
+                column: Column::ScoreAge,
+                width: None
+            })
+        );
+        assert!("pr:wide".parse::<ColumnSpec>().is_err());
+        assert!(
+            "size"
+                .parse::<ColumnSpec>()
+                .unwrap_err()
+                .contains("changed-files")
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let columns = |args: &[&str]| match Ateam::parse_from(args).cmd {
+            Command::Pr(options) | Command::Tui(options) => options
+                .columns
+                .iter()
+                .map(|spec| spec.column)
+                .collect::<Vec<_>>(),
+            Command::Followup(_) => vec![],
+        };
+        temp_env::with_var("ATEAM_COLUMNS", None::<&str>, || {
+            assert_eq!(columns(&["ateam", "pr"]).len(), DEFAULT_COLUMNS.len());
+            assert_eq!(
+                columns(&["ateam", "pr", "--columns", "title,repo"]),
+                vec![Column::Title, Column::Repo]
+            );
+        });
+        temp_env::with_var("ATEAM_COLUMNS", Some("author,score"), || {
+            assert_eq!(
+                columns(&["ateam", "pr"]),
+                vec![Column::Author, Column::Score]
+            );
+        });
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m"), Ok(Duration::minutes(30)));
+        assert_eq!(parse_duration("6h"), Ok(Duration::hours(6)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::days(2)));
+        assert_eq!(parse_duration("3w"), Ok(Duration::weeks(3)));
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("2y").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_overflow() {
+        assert_eq!(
+            parse_duration("99999999999999d"),
+            Err("duration too large \"99999999999999d\"".to_string())
+        );
+        assert!(parse_duration("99999999999999w").is_err());
+        assert!(parse_duration("9999999999w").is_err());
+        assert!(parse_duration("99999999999999999999m").is_err());
+        assert!("9999999999w".parse::<PointInTime>().is_err());
+        assert_eq!(
+            PointInTime::Ago(Duration::MAX).resolve(Utc::now()),
+            DateTime::<Utc>::MIN_UTC
+        );
+    }
+
+    #[test]
+    fn test_point_in_time_from_str() {
+        assert_eq!(
+            "2024-01-31".parse::<PointInTime>(),
+            Ok(PointInTime::Date(
+                NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+            ))
+        );
+        assert_eq!(
+            "2d".parse::<PointInTime>(),
+            Ok(PointInTime::Ago(Duration::days(2)))
+        );
+        assert!("yesterday".parse::<PointInTime>().is_err());
+    }
+
+    #[test]
+    fn test_point_in_time_resolve() {
+        let now = "2024-02-10T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(
+            PointInTime::Ago(Duration::hours(6))
+                .resolve(now)
+                .to_rfc3339(),
+            "2024-02-10T06:00:00+00:00"
+        );
+        assert_eq!(
+            PointInTime::Date(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap())
+                .resolve(now)
+                .to_rfc3339(),
+            "2024-01-31T00:00:00+00:00"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use super::super::cli::PrArgs;
+use super::super::filter::{Filter, Stage};
+use super::super::types::Pr;
+use super::teams::{self, Team};
+use anyhow::{Result, anyhow};
+
+/// The author filters: --author, --exclude-author, --author-team and --exclude-bots.
+/// The members of the --author-team are fetched once per run.
+#[derive(Debug, Default)]
+pub struct AuthorFilter {
+    authors: Vec<String>,
+    excluded: Vec<String>,
+    team_members: Option<Vec<String>>,
+    bots: Option<Vec<String>>,
+}
+
+impl AuthorFilter {
+    pub async fn new(github_api_token: &str, teams: &[Team], options: &PrArgs) -> Result<Self> {
+        let team_members = match &options.author_team {
+            Some(name) => {
+                let team = author_team(name, teams, options.org.as_ref())?;
+                Some(teams::members(github_api_token, &team).await?)
+            }
+            None => None,
+        };
+        Ok(AuthorFilter {
+            authors: options.author.clone(),
+            excluded: options.exclude_author.clone(),
+            team_members,
+            bots: options.exclude_bots.then(|| options.bots.clone()),
+        })
+    }
+
+    /// True if the pull requests of this author pass the filters
+    pub fn includes(&self, login: &str, is_bot: bool) -> bool {
+        (self.authors.is_empty() || contains(&self.authors, login))
+            && !contains(&self.excluded, login)
+            && self
+                .team_members
+                .as_ref()
+                .is_none_or(|members| contains(members, login))
+            && self
+                .bots
+                .as_ref()
+                .is_none_or(|bots| !is_bot && !is_bot_login(bots, login))
+    }
+}
+
+impl Filter for AuthorFilter {
+    fn name(&self) -> String {
+        "authors".to_string()
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Search
+    }
+
+    fn keep(&self, pr: &Pr) -> bool {
+        self.includes(&pr.author, pr.bot)
+    }
+}
+
+fn contains(logins: &[String], login: &str) -> bool {
+    logins.iter().any(|l| l.eq_ignore_ascii_case(login))
+}
+
+fn is_bot_login(bots: &[String], login: &str) -> bool {
+    login.ends_with("[bot]") || contains(bots, login)
+}
+
+/// The team of --author-team: ORG/SLUG, or the SLUG of one of my teams or of a team of --org
+fn author_team(name: &str, teams: &[Team], org: Option<&String>) -> Result<Team> {
+    if let Some((org, slug)) = name.split_once('/') {
+        return Ok(Team {
+            org: org.to_string(),
+            slug: slug.to_string(),
+        });
+    }
+    teams
+        .iter()
+        .find(|team| team.slug.eq_ignore_ascii_case(name))
+        .cloned()
+        .or_else(|| {
+            org.map(|org| Team {
+                org: org.clone(),
+                slug: name.to_string(),
+            })
+        })
+        .ok_or_else(|| {
+            anyhow!("Unknown team {name}: use ORG/SLUG or select the organization with --org")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logins(logins: &[&str]) -> Vec<String> {
+        logins.iter().map(ToString::to_string).collect()
+    }
+
+    fn team(org: &str, slug: &str) -> Team {
+        Team {
+            org: org.to_string(),
+            slug: slug.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_includes_without_filters() {
+        let filter = AuthorFilter::default();
+        assert!(filter.includes("alice", false));
+        assert!(filter.includes("dependabot", true));
+    }
+
+    #[test]
+    fn test_includes_authors() {
+        let filter = AuthorFilter {
+            authors: logins(&["alice", "bob"]),
+            excluded: logins(&["bob"]),
+            ..AuthorFilter::default()
+        };
+        assert!(filter.includes("Alice", false));
+        assert!(!filter.includes("bob", false));
+        assert!(!filter.includes("carol", false));
+    }
+
+    #[test]
+    fn test_includes_team_members() {
+        let filter = AuthorFilter {
+            team_members: Some(logins(&["alice"])),
+            ..AuthorFilter::default()
+        };
+        assert!(filter.includes("alice", false));
+        assert!(!filter.includes("bob", false));
+    }
+
+    #[test]
+    fn test_includes_bots() {
+        let filter = AuthorFilter {
+            bots: Some(logins(&["renovate"])),
+            ..AuthorFilter::default()
+        };
+        assert!(filter.includes("alice", false));
+        assert!(!filter.includes("dependabot", true));
+        assert!(!filter.includes("renovate", false));
+        assert!(!filter.includes("ci-helper[bot]", false));
+    }
+
+    #[test]
+    fn test_author_team() {
+        let teams = vec![team("acme", "backend")];
+        assert_eq!(
+            author_team("other/frontend", &teams, None).unwrap(),
+            team("other", "frontend")
+        );
+        assert_eq!(
+            author_team("Backend", &teams, None).unwrap(),
+            team("acme", "backend")
+        );
+        assert_eq!(
+            author_team("frontend", &teams, Some(&"acme".to_string())).unwrap(),
+            team("acme", "frontend")
+        );
+        assert!(author_team("frontend", &teams, None).is_err());
+    }
+}
@@ -1,8 +1,9 @@
 use std::fmt::Write;
 
 use anyhow::{Result, anyhow};
-use futures::stream::{FuturesUnordered, StreamExt};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use graphql_client::{GraphQLQuery, Response};
+use tokio::sync::Semaphore;
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -12,34 +13,42 @@ use graphql_client::{GraphQLQuery, Response};
 )]
 pub struct Blame;
 
+/// How many blame queries run at the same time, for all the pull requests
+pub const CONCURRENCY: usize = 8;
+
+/// True if the user is one of the authors of the files. It stops at the first file they authored.
+/// The `permits` limit the blame queries running at the same time.
+/// With `progress`, a dot is printed on stderr for every query.
 pub async fn blame(
     github_api_token: &str,
     repo_name: &str,
     repo_owner: &str,
     files: &[String],
     login: &str,
-) -> bool {
-    let blame_checks: FuturesUnordered<_> = files
+    permits: &Semaphore,
+    progress: bool,
+) -> Result<bool> {
+    let checks: Vec<_> = files
         .iter()
         .map(|file| async move {
-            eprint!(".");
+            let _permit = permits.acquire().await?;
+            if progress {
+                eprint!(".");
+            }
 
-            let response_data: blame::ResponseData =
-                match girhub_blame(github_api_token, repo_name, repo_owner, file).await {
-                    Ok(data) => data,
-                    Err(error) => panic!("Can't get the authors for {file}: {error}"),
-                };
-            is_file_author(&response_data, login)
+            let response_data = girhub_blame(github_api_token, repo_name, repo_owner, file)
+                .await
+                .map_err(|error| anyhow!("Can't get the authors for {file}: {error}"))?;
+            Ok::<bool, anyhow::Error>(is_file_author(&response_data, login))
         })
         .collect();
-
-    // The following execute the futures in parallel before apply .any(). Could be optimised by not
-    // waiting for all the futures to complete.
-    blame_checks
-        .collect::<Vec<bool>>()
-        .await
-        .into_iter()
-        .any(|b| b)
+    let mut authored = stream::iter(checks).buffer_unordered(CONCURRENCY);
+    while let Some(is_author) = authored.try_next().await? {
+        if is_author {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
 
 fn is_file_author(response_data: &blame::ResponseData, login: &str) -> bool {
@@ -0,0 +1,252 @@
+use std::fmt::Write;
+
+use super::super::types::TestsState;
+use anyhow::{Result, anyhow};
+use graphql_client::{GraphQLQuery, Response};
+use serde::Deserialize;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/checks.graphql",
+    response_derives = "Debug",
+    extern_enums("StatusState", "CheckStatusState", "CheckConclusionState")
+)]
+pub struct Checks;
+
+// The following enums are shared by all the queries fetching commit statuses and check runs,
+// so the conversion to TestsState is written only once.
+
+#[derive(Deserialize, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum StatusState {
+    ERROR,
+    EXPECTED,
+    FAILURE,
+    PENDING,
+    SUCCESS,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+pub enum CheckStatusState {
+    COMPLETED,
+    IN_PROGRESS,
+    PENDING,
+    QUEUED,
+    REQUESTED,
+    WAITING,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+pub enum CheckConclusionState {
+    ACTION_REQUIRED,
+    CANCELLED,
+    FAILURE,
+    NEUTRAL,
+    SKIPPED,
+    STALE,
+    STARTUP_FAILURE,
+    SUCCESS,
+    TIMED_OUT,
+    #[serde(other)]
+    Other,
+}
+
+/// A commit status context or a check run of the last commit of a pull request.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub state: TestsState,
+}
+
+pub fn tests_state(state: &StatusState) -> TestsState {
+    match state {
+        StatusState::SUCCESS => TestsState::Success,
+        StatusState::PENDING | StatusState::EXPECTED => TestsState::Pending,
+        StatusState::FAILURE | StatusState::ERROR => TestsState::Failure,
+        StatusState::Other => TestsState::None,
+    }
+}
+
+pub fn check_run_state(
+    status: &CheckStatusState,
+    conclusion: Option<&CheckConclusionState>,
+) -> TestsState {
+    match (status, conclusion) {
+        (CheckStatusState::COMPLETED, Some(conclusion)) => match conclusion {
+            CheckConclusionState::SUCCESS
+            | CheckConclusionState::NEUTRAL
+            | CheckConclusionState::SKIPPED => TestsState::Success,
+            CheckConclusionState::ACTION_REQUIRED
+            | CheckConclusionState::CANCELLED
+            | CheckConclusionState::FAILURE
+            | CheckConclusionState::STARTUP_FAILURE
+            | CheckConclusionState::TIMED_OUT => TestsState::Failure,
+            CheckConclusionState::STALE | CheckConclusionState::Other => TestsState::None,
+        },
+        (CheckStatusState::COMPLETED | CheckStatusState::Other, None) => TestsState::None,
+        _ => TestsState::Pending,
+    }
+}
+
+/// Fetch the status contexts and check runs not returned by the first page of the search.
+pub async fn remaining_checks(
+    github_api_token: &str,
+    pr_id: &str,
+    after: Option<String>,
+) -> Result<Vec<Check>> {
+    let mut checks = vec![];
+    let mut cursor = after;
+    loop {
+        let response_data = github_checks(github_api_token, pr_id, cursor.clone()).await?;
+        let (mut page, next_cursor) = parse(response_data);
+        checks.append(&mut page);
+        if next_cursor.is_none() {
+            break;
+        }
+        cursor = next_cursor;
+    }
+    Ok(checks)
+}
+
+async fn github_checks(
+    github_api_token: &str,
+    pr_id: &str,
+    after: Option<String>,
+) -> Result<checks::ResponseData> {
+    let q = Checks::build_query(checks::Variables {
+        id: pr_id.to_string(),
+        after,
+    });
+
+    let res = super::call(github_api_token, &q).await?;
+
+    let response_body: Response<checks::ResponseData> = res.json().await?;
+
+    if let Some(errors) = response_body.errors {
+        let mut error_str = String::new();
+        for error in &errors {
+            write!(error_str, "{error:?}").unwrap();
+        }
+        Err(anyhow!("Errors fetching the checks of {pr_id} {error_str}"))
+    } else {
+        match response_body.data {
+            Some(data) => Ok(data),
+            None => Err(anyhow!(
+                "Missing response data fetching the checks of {pr_id}"
+            )),
+        }
+    }
+}
+
+fn parse(response_data: checks::ResponseData) -> (Vec<Check>, Option<String>) {
+    let contexts = match response_data.node {
+        Some(checks::ChecksNode::PullRequest(pr)) => pr
+            .commits
+            .nodes
+            .into_iter()
+            .flatten()
+            .flatten()
+            .next()
+            .and_then(|node| node.commit.status_check_rollup)
+            .map(|rollup| rollup.contexts),
+        _ => None,
+    };
+
+    match contexts {
+        Some(contexts) => (
+            contexts
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(check)
+                .collect(),
+            if contexts.page_info.has_next_page {
+                contexts.page_info.end_cursor
+            } else {
+                None
+            },
+        ),
+        None => (vec![], None),
+    }
+}
+
+fn check(
+    node: checks::ChecksNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes,
+) -> Check {
+    match node {
+        checks::ChecksNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes::StatusContext(status_context) => Check {
+            state: tests_state(&status_context.state),
+            name: status_context.context,
+        },
+        checks::ChecksNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes::CheckRun(check_run) => Check {
+            state: check_run_state(&check_run.status, check_run.conclusion.as_ref()),
+            name: check_run.name,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_run_state_completed() {
+        assert!(matches!(
+            check_run_state(
+                &CheckStatusState::COMPLETED,
+                Some(&CheckConclusionState::SUCCESS)
+            ),
+            TestsState::Success
+        ));
+        assert!(matches!(
+            check_run_state(
+                &CheckStatusState::COMPLETED,
+                Some(&CheckConclusionState::SKIPPED)
+            ),
+            TestsState::Success
+        ));
+        assert!(matches!(
+            check_run_state(
+                &CheckStatusState::COMPLETED,
+                Some(&CheckConclusionState::TIMED_OUT)
+            ),
+            TestsState::Failure
+        ));
+        assert!(matches!(
+            check_run_state(&CheckStatusState::COMPLETED, None),
+            TestsState::None
+        ));
+    }
+
+    #[test]
+    fn test_check_run_state_not_completed() {
+        assert!(matches!(
+            check_run_state(&CheckStatusState::IN_PROGRESS, None),
+            TestsState::Pending
+        ));
+        assert!(matches!(
+            check_run_state(&CheckStatusState::QUEUED, None),
+            TestsState::Pending
+        ));
+    }
+
+    #[test]
+    fn test_tests_state() {
+        assert!(matches!(
+            tests_state(&StatusState::EXPECTED),
+            TestsState::Pending
+        ));
+        assert!(matches!(
+            tests_state(&StatusState::ERROR),
+            TestsState::Failure
+        ));
+    }
+}
@@ -0,0 +1,554 @@
+use std::fmt::Write;
+use std::future::Future;
+
+use super::super::types::Label;
+use super::repo_view;
+use super::teams::Team;
+use anyhow::{Result, anyhow};
+use futures::try_join;
+use graphql_client::{GraphQLQuery, Response};
+use serde::Deserialize;
+
+// The search query returns only the first page of the connections nested in a pull request.
+// The following queries fetch the other pages, so every field of Pr is computed from complete data.
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/connections.graphql",
+    response_derives = "Debug"
+)]
+pub struct ReviewThreadsPage;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/connections.graphql",
+    response_derives = "Debug",
+    extern_enums("PullRequestReviewState")
+)]
+pub struct ReviewsPage;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/connections.graphql",
+    response_derives = "Debug"
+)]
+pub struct LabelsPage;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/connections.graphql",
+    response_derives = "Debug"
+)]
+pub struct ReviewRequestsPage;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/connections.graphql",
+    response_derives = "Debug"
+)]
+pub struct FilesPage;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+pub enum PullRequestReviewState {
+    APPROVED,
+    CHANGES_REQUESTED,
+    COMMENTED,
+    DISMISSED,
+    PENDING,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug)]
+pub struct ReviewThread {
+    pub is_resolved: bool,
+    pub is_outdated: bool,
+}
+
+#[derive(Debug)]
+pub struct PrReview {
+    pub author: Option<String>,
+    pub state: PullRequestReviewState,
+}
+
+#[derive(Debug)]
+pub enum Reviewer {
+    User(String),
+    Team(Team),
+    Mannequin,
+}
+
+#[derive(Debug)]
+pub struct ReviewRequest {
+    pub as_code_owner: bool,
+    // None when the token cannot read the requested reviewer
+    pub reviewer: Option<Reviewer>,
+}
+
+/// The complete connections nested in a pull request
+#[derive(Debug)]
+pub struct Connections {
+    pub review_threads: Vec<ReviewThread>,
+    pub reviews: Vec<PrReview>,
+    pub labels: Vec<Label>,
+    pub review_requests: Vec<ReviewRequest>,
+}
+
+/// The items of a page and the cursor of the next page, if there is one
+type Page<T> = (Vec<T>, Option<String>);
+
+type PrNode = repo_view::RepoViewSearchEdgesNodeOnPullRequest;
+
+/// Complete the connections of the pull request, fetching their missing pages.
+pub async fn complete(github_api_token: &str, pr: &PrNode) -> Result<Connections> {
+    let id = pr.id.as_str();
+    let (review_threads, reviews, labels, review_requests) = try_join!(
+        all_pages(first_review_threads(pr), |after| review_threads_page(
+            github_api_token,
+            id,
+            after
+        )),
+        all_pages(first_reviews(pr), |after| reviews_page(
+            github_api_token,
+            id,
+            after
+        )),
+        all_pages(first_labels(pr), |after| labels_page(
+            github_api_token,
+            id,
+            after
+        )),
+        all_review_requests(github_api_token, pr),
+    )?;
+
+    Ok(Connections {
+        review_threads,
+        reviews,
+        labels,
+        review_requests,
+    })
+}
+
+async fn all_pages<T, F, Fut>(first: Page<T>, next: F) -> Result<Vec<T>>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<Page<T>>>,
+{
+    let (mut items, mut cursor) = first;
+    while let Some(after) = cursor {
+        let (mut page, next_cursor) = next(after).await?;
+        items.append(&mut page);
+        cursor = next_cursor;
+    }
+    Ok(items)
+}
+
+fn next_cursor(has_next_page: bool, end_cursor: Option<&String>) -> Option<String> {
+    if has_next_page {
+        end_cursor.cloned()
+    } else {
+        None
+    }
+}
+
+async fn fetch<Q: GraphQLQuery>(
+    github_api_token: &str,
+    variables: Q::Variables,
+) -> Result<Q::ResponseData> {
+    let q = Q::build_query(variables);
+    let res = super::call(github_api_token, &q).await?;
+
+    let response_body: Response<Q::ResponseData> = res.json().await?;
+
+    if let Some(errors) = response_body.errors {
+        let mut error_str = String::new();
+        for error in &errors {
+            write!(error_str, "{error:?}").unwrap();
+        }
+        Err(anyhow!(
+            "Errors fetching a page of a connection {error_str}"
+        ))
+    } else {
+        match response_body.data {
+            Some(data) => Ok(data),
+            None => Err(anyhow!(
+                "Missing response data fetching a page of a connection"
+            )),
+        }
+    }
+}
+
+fn first_review_threads(pr: &PrNode) -> Page<ReviewThread> {
+    (
+        pr.review_threads
+            .nodes
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|thread| ReviewThread {
+                is_resolved: thread.is_resolved,
+                is_outdated: thread.is_outdated,
+            })
+            .collect(),
+        next_cursor(
+            pr.review_threads.page_info.has_next_page,
+            pr.review_threads.page_info.end_cursor.as_ref(),
+        ),
+    )
+}
+
+async fn review_threads_page(
+    github_api_token: &str,
+    id: &str,
+    after: String,
+) -> Result<Page<ReviewThread>> {
+    let data = fetch::<ReviewThreadsPage>(
+        github_api_token,
+        review_threads_page::Variables {
+            id: id.to_string(),
+            after: Some(after),
+        },
+    )
+    .await?;
+    Ok(match data.node {
+        Some(review_threads_page::ReviewThreadsPageNode::PullRequest(pr)) => (
+            pr.review_threads
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|thread| ReviewThread {
+                    is_resolved: thread.is_resolved,
+                    is_outdated: thread.is_outdated,
+                })
+                .collect(),
+            next_cursor(
+                pr.review_threads.page_info.has_next_page,
+                pr.review_threads.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        _ => (vec![], None),
+    })
+}
+
+/// The first page of reviews, returned by the search
+pub fn first_reviews(pr: &PrNode) -> Page<PrReview> {
+    match &pr.reviews {
+        Some(reviews) => (
+            reviews
+                .nodes
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|review| PrReview {
+                    author: review.author.as_ref().map(|author| author.login.clone()),
+                    state: review.state.clone(),
+                })
+                .collect(),
+            next_cursor(
+                reviews.page_info.has_next_page,
+                reviews.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        None => (vec![], None),
+    }
+}
+
+async fn reviews_page(github_api_token: &str, id: &str, after: String) -> Result<Page<PrReview>> {
+    let data = fetch::<ReviewsPage>(
+        github_api_token,
+        reviews_page::Variables {
+            id: id.to_string(),
+            after: Some(after),
+        },
+    )
+    .await?;
+    Ok(match data.node {
+        Some(reviews_page::ReviewsPageNode::PullRequest(
+            reviews_page::ReviewsPageNodeOnPullRequest {
+                reviews: Some(reviews),
+            },
+        )) => (
+            reviews
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|review| PrReview {
+                    author: review.author.map(|author| author.login),
+                    state: review.state,
+                })
+                .collect(),
+            next_cursor(
+                reviews.page_info.has_next_page,
+                reviews.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        _ => (vec![], None),
+    })
+}
+
+/// The number of changed files checked by blame, as it makes a query for every file
+const BLAME_FILES: usize = 5;
+
+/// All the changed files of the pull request, fetching their missing pages.
+pub async fn all_files(github_api_token: &str, pr: &PrNode) -> Result<Vec<String>> {
+    let id = pr.id.as_str();
+    all_pages(first_files(pr), |after| {
+        files_page(github_api_token, id, after)
+    })
+    .await
+}
+
+/// The first changed files, the ones checked by blame
+pub fn blame_files(files: &[String]) -> &[String] {
+    &files[..files.len().min(BLAME_FILES)]
+}
+
+fn first_files(pr: &PrNode) -> Page<String> {
+    match &pr.files {
+        Some(files) => (
+            files
+                .nodes
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|file| file.path.clone())
+                .collect(),
+            next_cursor(
+                files.page_info.has_next_page,
+                files.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        None => (vec![], None),
+    }
+}
+
+async fn files_page(github_api_token: &str, id: &str, after: String) -> Result<Page<String>> {
+    let data = fetch::<FilesPage>(
+        github_api_token,
+        files_page::Variables {
+            id: id.to_string(),
+            after: Some(after),
+        },
+    )
+    .await?;
+    Ok(match data.node {
+        Some(files_page::FilesPageNode::PullRequest(files_page::FilesPageNodeOnPullRequest {
+            files: Some(files),
+        })) => (
+            files
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|file| file.path)
+                .collect(),
+            next_cursor(
+                files.page_info.has_next_page,
+                files.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        _ => (vec![], None),
+    })
+}
+
+fn first_labels(pr: &PrNode) -> Page<Label> {
+    match &pr.labels {
+        Some(labels) => (
+            labels
+                .nodes
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|label| Label {
+                    name: label.name.clone(),
+                    color: label.color.clone(),
+                })
+                .collect(),
+            next_cursor(
+                labels.page_info.has_next_page,
+                labels.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        None => (vec![], None),
+    }
+}
+
+async fn labels_page(github_api_token: &str, id: &str, after: String) -> Result<Page<Label>> {
+    let data = fetch::<LabelsPage>(
+        github_api_token,
+        labels_page::Variables {
+            id: id.to_string(),
+            after: Some(after),
+        },
+    )
+    .await?;
+    Ok(match data.node {
+        Some(labels_page::LabelsPageNode::PullRequest(
+            labels_page::LabelsPageNodeOnPullRequest {
+                labels: Some(labels),
+            },
+        )) => (
+            labels
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|label| Label {
+                    name: label.name,
+                    color: label.color,
+                })
+                .collect(),
+            next_cursor(
+                labels.page_info.has_next_page,
+                labels.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        _ => (vec![], None),
+    })
+}
+
+async fn all_review_requests(github_api_token: &str, pr: &PrNode) -> Result<Vec<ReviewRequest>> {
+    let id = pr.id.as_str();
+    all_pages(first_review_requests(pr), |after| {
+        review_requests_page(github_api_token, id, after)
+    })
+    .await
+}
+
+fn first_review_requests(pr: &PrNode) -> Page<ReviewRequest> {
+    use repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewRequestsNodesRequestedReviewer as RequestedReviewer;
+
+    match &pr.review_requests {
+        Some(requests) => (
+            requests
+                .nodes
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|request| ReviewRequest {
+                    as_code_owner: request.as_code_owner,
+                    reviewer: request
+                        .requested_reviewer
+                        .as_ref()
+                        .map(|reviewer| match reviewer {
+                            RequestedReviewer::User(user) => Reviewer::User(user.login.clone()),
+                            RequestedReviewer::Team(team) => Reviewer::Team(Team {
+                                org: team.organization.login.clone(),
+                                slug: team.slug.clone(),
+                            }),
+                            RequestedReviewer::Mannequin => Reviewer::Mannequin,
+                        }),
+                })
+                .collect(),
+            next_cursor(
+                requests.page_info.has_next_page,
+                requests.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        None => (vec![], None),
+    }
+}
+
+async fn review_requests_page(
+    github_api_token: &str,
+    id: &str,
+    after: String,
+) -> Result<Page<ReviewRequest>> {
+    use review_requests_page::ReviewRequestsPageNodeOnPullRequestReviewRequestsNodesRequestedReviewer as RequestedReviewer;
+
+    let data = fetch::<ReviewRequestsPage>(
+        github_api_token,
+        review_requests_page::Variables {
+            id: id.to_string(),
+            after: Some(after),
+        },
+    )
+    .await?;
+    Ok(match data.node {
+        Some(review_requests_page::ReviewRequestsPageNode::PullRequest(
+            review_requests_page::ReviewRequestsPageNodeOnPullRequest {
+                review_requests: Some(requests),
+            },
+        )) => (
+            requests
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|request| ReviewRequest {
+                    as_code_owner: request.as_code_owner,
+                    reviewer: request.requested_reviewer.map(|reviewer| match reviewer {
+                        RequestedReviewer::User(user) => Reviewer::User(user.login),
+                        RequestedReviewer::Team(team) => Reviewer::Team(Team {
+                            org: team.organization.login,
+                            slug: team.slug,
+                        }),
+                        RequestedReviewer::Mannequin => Reviewer::Mannequin,
+                    }),
+                })
+                .collect(),
+            next_cursor(
+                requests.page_info.has_next_page,
+                requests.page_info.end_cursor.as_ref(),
+            ),
+        ),
+        _ => (vec![], None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_next_cursor() {
+        let cursor = "abc".to_string();
+        assert_eq!(next_cursor(true, Some(&cursor)), Some(cursor.clone()));
+        assert_eq!(next_cursor(false, Some(&cursor)), None);
+        assert_eq!(next_cursor(true, None), None);
+    }
+
+    #[test]
+    fn test_all_pages() {
+        let calls = Cell::new(0);
+        let items =
+            futures::executor::block_on(all_pages((vec![1, 2], Some("2".to_string())), |after| {
+                calls.set(calls.get() + 1);
+                async move {
+                    Ok(match after.as_str() {
+                        "2" => (vec![3, 4], Some("4".to_string())),
+                        _ => (vec![5], None),
+                    })
+                }
+            }))
+            .unwrap();
+        assert_eq!(items, vec![1, 2, 3, 4, 5]);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_all_pages_single_page() {
+        let items = futures::executor::block_on(all_pages((vec![1], None), |_| async {
+            Ok((vec![2], None))
+        }))
+        .unwrap();
+        assert_eq!(items, vec![1]);
+    }
+
+    #[test]
+    fn test_blame_files() {
+        let files: Vec<String> = (1..=7).map(|n| format!("src/{n}.rs")).collect();
+        assert_eq!(blame_files(&files), &files[..5]);
+        assert_eq!(blame_files(&files[..2]), &files[..2]);
+        assert!(blame_files(&[]).is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 use super::super::types::{Review, ReviewState};
-use anyhow::Result;
+use super::query::SearchQuery;
+use anyhow::{Context, Result};
 use graphql_client::{GraphQLQuery, Response};
 
 #[derive(GraphQLQuery)]
@@ -13,20 +14,34 @@ pub struct Followup;
 #[allow(clippy::upper_case_acronyms)]
 type URI = String;
 
-pub async fn followup(github_api_token: &str, login: &str) -> Vec<Review> {
-    let response_data: followup::ResponseData = match girhub_followup(github_api_token, login).await
-    {
-        Ok(data) => data,
-        Err(e) => panic!("Can't get the follow up actions: {e:?}"),
-    };
+pub async fn followup(github_api_token: &str, login: &str, debug: bool) -> Result<Vec<Review>> {
+    let query = github_query(login);
+    if debug {
+        eprintln!(">> GitHub query: {query}");
+    }
+    let response_data = girhub_followup(github_api_token, login, &query)
+        .await
+        .context("Can't get the follow up actions")?;
+
+    Ok(parse(&response_data, login))
+}
 
-    parse(&response_data, login)
+/// The open pull requests reviewed by the user and authored by someone else
+pub fn github_query(login: &str) -> SearchQuery {
+    SearchQuery::open_pull_requests()
+        .draft(false)
+        .reviewed_by(login)
+        .not_author(login)
 }
 
-async fn girhub_followup(github_api_token: &str, login: &str) -> Result<followup::ResponseData> {
+async fn girhub_followup(
+    github_api_token: &str,
+    login: &str,
+    query: &SearchQuery,
+) -> Result<followup::ResponseData> {
     let q = Followup::build_query(followup::Variables {
         login: login.to_string(),
-        query: format!("is:pr is:open draft:false reviewed-by:{login} -author:{login}"),
+        query: query.to_string(),
     });
 
     let res = super::call(github_api_token, &q).await?;
@@ -40,7 +55,7 @@ async fn girhub_followup(github_api_token: &str, login: &str) -> Result<followup
             println!("{error:?}");
         }
     }
-    Ok(response_body.data.expect("missing response data"))
+    response_body.data.context("missing response data")
 }
 
 fn parse(response_data: &followup::ResponseData, login: &str) -> Vec<Review> {
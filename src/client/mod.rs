@@ -1,23 +1,46 @@
 use std::fmt::Write;
 
 use super::cli::PrArgs;
-use super::types::{Files, Label, Labels, Pr, ReviewRequested, Score, ScoredPr, TestsState};
+use super::types::{Files, Labels, MergeState, Pr, ReviewRequested, Score, ScoredPr, TestsState};
 use anyhow::{Result, anyhow};
 use chrono::prelude::{DateTime as DT, Utc};
 use graphql_client::{GraphQLQuery, QueryBody, Response};
 use itertools::Itertools;
 use regex::Regex;
+mod authors;
 mod blame;
+mod checks;
+use checks::{Check, CheckConclusionState, CheckStatusState, StatusState};
+mod connections;
+use connections::{
+    Connections, PrReview, PullRequestReviewState, ReviewRequest, ReviewThread, Reviewer,
+};
 pub mod followup;
+mod planner;
+pub mod query;
+use query::{DateRange, SearchQuery};
+mod rules;
+use rules::Rules;
+mod teams;
+use super::filter::{self, Pipeline, Stage};
+use teams::Team;
 pub mod username;
+use futures::future::try_join_all;
 use futures::join;
-use futures::stream::{FuturesUnordered, StreamExt};
+use futures::stream::{self, FuturesUnordered, StreamExt, TryStreamExt};
+use tokio::sync::Semaphore;
 
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/client/schema.graphql",
     query_path = "src/client/pr.graphql",
-    response_derives = "Debug"
+    response_derives = "Debug",
+    extern_enums(
+        "StatusState",
+        "CheckStatusState",
+        "CheckConclusionState",
+        "PullRequestReviewState"
+    )
 )]
 pub struct RepoView;
 
@@ -28,34 +51,145 @@ type DateTime = String;
 
 const AGENT: &str = concat!("ateam/", env!("CARGO_PKG_VERSION"));
 
+/// Called with every pull request kept by the filters, as soon as its page is processed
+pub type OnPr<'a> = &'a (dyn Fn(&ScoredPr) + Sync);
+
+/// What the pull requests of a run are fetched and filtered with
+struct Context<'a> {
+    github_api_token: &'a str,
+    username: &'a str,
+    options: &'a PrArgs,
+    teams: &'a [Team],
+    filters: &'a Pipeline,
+    rules: &'a Rules,
+    /// The blame queries running at the same time
+    blames: &'a Semaphore,
+    /// Print the progress of the fetch on stderr
+    progress: bool,
+}
+
+/// The pull requests kept by the filters, not sorted
+pub struct Fetched {
+    pub prs: Vec<ScoredPr>,
+    /// The pull requests returned by the searches, before the filters
+    pub fetched: usize,
+}
+
+/// The teams of the user. Only the team filters fail without them: otherwise, like when
+/// the organization enforces SAML, the team review requests are ignored.
+async fn my_teams(github_api_token: &str, username: &str, options: &PrArgs) -> Result<Vec<Team>> {
+    match teams::teams(github_api_token, username).await {
+        Err(error) if options.team.is_none() && options.author_team.is_none() => {
+            if options.debug {
+                eprintln!(">> Ignoring the team review requests: {error:#}");
+            }
+            Ok(vec![])
+        }
+        teams => teams,
+    }
+}
+
 pub async fn fetch_scored_prs(
     github_api_token: &str,
     username: &str,
     options: &PrArgs,
+    on_pr: Option<OnPr<'_>>,
+    progress: bool,
+) -> Result<Fetched> {
+    let teams = my_teams(github_api_token, username, options).await?;
+    let mut filters = filter::filters(username, options)?;
+    filters.push(Box::new(
+        authors::AuthorFilter::new(github_api_token, &teams, options).await?,
+    ));
+    let filters = Pipeline::new(filters);
+    let searches: Vec<String> = try_join_all(any_queries(options).into_iter().map(|any_query| {
+        planner::plan(
+            github_api_token,
+            github_query(username, options, &options.repo, any_query),
+            options
+                .repo
+                .iter()
+                .map(|repo| github_query(username, options, std::slice::from_ref(repo), any_query))
+                .collect(),
+        )
+    }))
+    .await?
+    .into_iter()
+    .flatten()
+    .map(|search| search.to_string())
+    .unique()
+    .collect();
+    if options.debug {
+        for search in &searches {
+            eprintln!(">> GitHub query: {search}");
+        }
+    }
+
+    let rules = Rules::default();
+    let blames = Semaphore::new(blame::CONCURRENCY);
+    let context = Context {
+        github_api_token,
+        username,
+        options,
+        teams: &teams,
+        filters: &filters,
+        rules: &rules,
+        blames: &blames,
+        progress,
+    };
+    let fetches: Vec<_> = searches
+        .iter()
+        .map(|search| fetch_search(&context, search, on_pr))
+        .collect();
+    let list_prs: Vec<Vec<ScoredPr>> = stream::iter(fetches)
+        .buffer_unordered(planner::CONCURRENCY)
+        .try_collect()
+        .await?;
+    if progress {
+        eprintln!();
+    }
+    if options.debug {
+        eprintln!("{}", filters.stats());
+    }
+
+    // The same pull request can be returned by more than one search
+    Ok(Fetched {
+        prs: list_prs
+            .into_iter()
+            .flatten()
+            .unique_by(|spr| spr.pr.url.clone())
+            .collect(),
+        fetched: filters.checked(),
+    })
+}
+
+async fn fetch_search(
+    context: &Context<'_>,
+    search: &str,
+    on_pr: Option<OnPr<'_>>,
 ) -> Result<Vec<ScoredPr>> {
     let mut list_prs: Vec<Vec<ScoredPr>> = vec![];
     let mut list_data: Vec<repo_view::ResponseData> = vec![];
     let mut cursor = None;
     let mut first = true;
     loop {
-        eprint!(".");
+        if context.progress {
+            eprint!(".");
+        }
 
         let o_get_ranked_prs = if first {
             None
         } else {
-            list_data.pop().map(|data| {
-                ranked_prs(
-                    github_api_token,
-                    username,
-                    options.required_approvals,
-                    options,
-                    data,
-                )
-            })
+            list_data.pop().map(|data| ranked_prs(context, data))
         };
 
         let o_get_next_response_data_and_cursor = if first || cursor.is_some() {
-            Some(query(github_api_token, username, options, cursor.clone()))
+            Some(query(
+                context.github_api_token,
+                search,
+                context.options,
+                cursor.clone(),
+            ))
         } else {
             None
         };
@@ -67,13 +201,13 @@ pub async fn fetch_scored_prs(
                 o_get_ranked_prs.unwrap(),
                 o_get_next_response_data_and_cursor.unwrap()
             );
-            list_prs.push(prs);
+            list_prs.push(notify(prs?, on_pr));
             let (new_response_data, new_cursor) = response_and_cursor?;
             cursor = new_cursor;
             list_data.push(new_response_data);
         } else if o_get_ranked_prs.is_some() {
             // Only one future to await
-            list_prs.push(o_get_ranked_prs.unwrap().await);
+            list_prs.push(notify(o_get_ranked_prs.unwrap().await?, on_pr));
         } else if o_get_next_response_data_and_cursor.is_some() {
             // Only one future to await
             #[allow(clippy::unnecessary_unwrap)]
@@ -87,18 +221,33 @@ pub async fn fetch_scored_prs(
 
         first = false;
     }
-    eprintln!();
 
     Ok(list_prs.into_iter().flatten().collect::<Vec<ScoredPr>>())
 }
 
+fn notify(sprs: Vec<ScoredPr>, on_pr: Option<OnPr<'_>>) -> Vec<ScoredPr> {
+    if let Some(on_pr) = on_pr {
+        sprs.iter().for_each(on_pr);
+    }
+    sprs
+}
+
 pub async fn call<V: serde::Serialize>(
     github_api_token: &str,
     q: &QueryBody<V>,
+) -> Result<reqwest::Response> {
+    call_with_accept(github_api_token, q, "application/json").await
+}
+
+async fn call_with_accept<V: serde::Serialize>(
+    github_api_token: &str,
+    q: &QueryBody<V>,
+    accept: &str,
 ) -> Result<reqwest::Response> {
     let client = reqwest::Client::builder().user_agent(AGENT).build()?;
     let res = client
         .post("https://api.github.com/graphql")
+        .header("Accept", accept)
         .json(&q)
         .bearer_auth(github_api_token)
         .send()
@@ -108,26 +257,30 @@ pub async fn call<V: serde::Serialize>(
 
 async fn query(
     github_api_token: &str,
-    username: &str,
+    search: &str,
     options: &PrArgs,
     after: Option<String>,
 ) -> Result<(repo_view::ResponseData, Option<String>)> {
-    let query_argument = github_query(username, options);
-    if options.debug {
-        println!(">> GitHub query: {query_argument:?}");
-    }
     let batch_size = limited_batch_size(options.batch_size);
     let q = RepoView::build_query(repo_view::Variables {
-        query: query_argument,
+        query: search.to_string(),
         first: batch_size,
         after,
-        num_checks: match options.tests_regex {
-            Some(_) => 20,
-            None => 0,
+        // The checks of the pull requests with required checks are fetched later, when needed
+        num_checks: if options.tests_regex.is_some() {
+            100
+        } else {
+            0
         },
     });
 
-    let res = call(github_api_token, &q).await?;
+    // mergeStateStatus is still a preview feature
+    let res = call_with_accept(
+        github_api_token,
+        &q,
+        "application/vnd.github.merge-info-preview+json",
+    )
+    .await?;
 
     let response_body: Response<repo_view::ResponseData> = res.json().await?;
     // println!("{:?}", response_body);
@@ -186,79 +339,85 @@ fn last_item_cursor(response_data: &repo_view::ResponseData, batch_size: i64) ->
     }
 }
 
-fn github_query(username: &str, options: &PrArgs) -> String {
-    format!(
-        // "is:pr is:open draft:false -status:progess -status:failure {}{}{}{}",
-        "is:pr is:open {}{}{}{}{}{}{}",
-        query_drafts(options.include_drafts),
-        query_mine(username, options.only_mine),
-        query_requested(username, options.requested),
-        query_labels(&options.label, &options.exclude_label),
-        query_repos(&options.repo),
-        query_org(options.org.as_ref()),
-        &options.query.join(" ")
-    )
-}
-
-fn query_drafts(include_drafts: bool) -> &'static str {
-    if include_drafts { "" } else { "draft:false " }
-}
-
-fn query_mine(username: &str, only_mine: bool) -> String {
-    if only_mine {
-        format!("author:{username} ")
+/// Every --any-query value is a separate search. None when there are no --any-query values.
+fn any_queries(options: &PrArgs) -> Vec<Option<&String>> {
+    if options.any_query.is_empty() {
+        vec![None]
     } else {
-        String::new()
+        options.any_query.iter().map(Some).collect()
     }
 }
 
-fn query_requested(username: &str, requested: bool) -> String {
-    if requested {
-        format!("review-requested:{username} ")
-    } else {
-        String::new()
-    }
+/// The GitHub searches of the options, before the planner splits them
+pub fn search_queries(username: &str, options: &PrArgs) -> Vec<String> {
+    any_queries(options)
+        .into_iter()
+        .map(|any_query| github_query(username, options, &options.repo, any_query).to_string())
+        .collect()
 }
 
-fn query_labels(labels: &[String], exclude_label: &[String]) -> String {
-    let mut result = String::new();
-    for label in labels {
-        write!(result, "label:\"{label}\" ").unwrap();
+fn github_query(
+    username: &str,
+    options: &PrArgs,
+    repos: &[String],
+    any_query: Option<&String>,
+) -> SearchQuery {
+    let mut query = SearchQuery::open_pull_requests();
+    if !options.include_drafts {
+        query = query.draft(false);
     }
-    for label in exclude_label {
-        write!(result, "-label:\"{label}\" ").unwrap();
+    if options.only_mine {
+        query = query.author(username);
+    }
+    if options.requested {
+        query = query.review_requested(username);
+    }
+    if options.involves_me {
+        query = query.involves(username);
+    }
+    if options.mentions_me {
+        query = query.mentions(username);
+    }
+    if options.commented_by_me {
+        query = query.commenter(username);
+    }
+    if let Some(updated_since) = &options.updated_since {
+        query = query.updated(DateRange::Since(updated_since.resolve(Utc::now())));
+    }
+    if let Some(created_before) = &options.created_before {
+        query = query.created(DateRange::Before(created_before.resolve(Utc::now())));
+    }
+    if let Some(base) = &options.base {
+        query = query.base(base);
+    }
+    for label in &options.label {
+        query = query.label(label);
+    }
+    for label in &options.exclude_label {
+        query = query.not_label(label);
     }
-    result
-}
-
-fn query_repos(repos: &[String]) -> String {
-    let mut result = String::new();
     for repo in repos {
-        write!(result, "repo:{repo} ").unwrap();
+        query = query.repo(repo);
     }
-    result
-}
-
-fn query_org(org: Option<&String>) -> String {
-    if let Some(org) = org {
-        format!("org:{org} ")
-    } else {
-        String::new()
+    if let Some(org) = &options.org {
+        query = query.org(org);
+    }
+    query = query.raw(&options.query.join(" "));
+    if let Some(any_query) = any_query {
+        query = query.raw(any_query);
     }
+    query
 }
 
 async fn ranked_prs(
-    github_api_token: &str,
-    username: &str,
-    required_approvals: u8,
-    options: &PrArgs,
+    context: &Context<'_>,
     response_data: repo_view::ResponseData,
-) -> Vec<ScoredPr> {
-    prs(github_api_token, username, options, response_data)
-        .await
+) -> Result<Vec<ScoredPr>> {
+    Ok(prs(context, response_data)
+        .await?
         .into_iter()
-        .map(|pr| scored_pr(required_approvals, pr))
-        .collect::<Vec<ScoredPr>>()
+        .map(scored_pr)
+        .collect::<Vec<ScoredPr>>())
 }
 
 pub fn sorted_ranked_prs(mut sprs: Vec<ScoredPr>) -> Vec<ScoredPr> {
@@ -268,23 +427,12 @@ pub fn sorted_ranked_prs(mut sprs: Vec<ScoredPr>) -> Vec<ScoredPr> {
     sprs
 }
 
-fn regex(regex_text: Option<&String>) -> Option<Regex> {
-    regex_text.and_then(|text| Regex::new(text).ok())
-}
-
-fn scored_pr(required_approvals: u8, pr: Pr) -> ScoredPr {
-    let s = Score::from_pr(required_approvals, &pr);
+fn scored_pr(pr: Pr) -> ScoredPr {
+    let s = Score::from_pr(pr.required_approvals, &pr);
     ScoredPr { pr, score: s }
 }
 
-async fn prs(
-    github_api_token: &str,
-    username: &str,
-    options: &PrArgs,
-    response_data: repo_view::ResponseData,
-) -> Vec<Pr> {
-    let re = regex(options.regex.as_ref());
-    let re_not = regex(options.regex_not.as_ref());
+async fn prs(context: &Context<'_>, response_data: repo_view::ResponseData) -> Result<Vec<Pr>> {
     let prs: FuturesUnordered<_> = response_data
         .search
         .edges
@@ -299,145 +447,222 @@ async fn prs(
             }
             _ => None,
         })
-        .filter(|i| {
-            include_pr(
-                i,
-                re.as_ref(),
-                re_not.as_ref(),
-                username,
-                options.include_mine,
-                options.only_mine,
-                options.include_reviewed_by_me,
-            )
-        })
-        .map(|i| async move { pr_stats(github_api_token, username, options, i).await })
+        .map(|i| async move { pr_stats(context, i).await })
         .collect();
 
-    prs.collect::<Vec<Option<Pr>>>()
+    Ok(prs
+        .collect::<Vec<Result<Option<Pr>>>>()
         .await
         .into_iter()
+        .collect::<Result<Vec<Option<Pr>>>>()?
+        .into_iter()
         .flatten()
-        .collect()
+        .collect())
 }
 
-fn include_pr(
-    pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest,
-    regex: Option<&Regex>,
-    regex_not: Option<&Regex>,
-    username: &str,
-    include_mine: bool,
-    only_mine: bool,
-    include_reviewed_by_me: bool,
-) -> bool {
-    !is_empty(pr)
-        && !has_conflicts(pr)
-        && regex_match(regex, true, pr)
-        && !regex_match(regex_not, false, pr)
-        && (include_mine || only_mine || author(pr) != username)
-        && (include_reviewed_by_me
-            || only_mine
-            || review_states(pr.reviews.as_ref(), username, true).is_empty())
-}
-
-fn regex_match(
-    regex: Option<&Regex>,
-    or: bool,
-    pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest,
-) -> bool {
-    match regex {
-        Some(re) => re.is_match(&pr.title),
-        None => or,
+/// The merge state of the pull request. GitHub computes the mergeability in the background,
+/// so it can be still unknown: the pull request is neither conflicting nor mergeable yet.
+fn merge_state(
+    mergeable: &repo_view::MergeableState,
+    status: &repo_view::MergeStateStatus,
+) -> MergeState {
+    match mergeable {
+        repo_view::MergeableState::CONFLICTING => return MergeState::Conflicting,
+        repo_view::MergeableState::UNKNOWN | repo_view::MergeableState::Other(_) => {
+            return MergeState::Unknown;
+        }
+        repo_view::MergeableState::MERGEABLE => {}
+    }
+    match status {
+        repo_view::MergeStateStatus::CLEAN => MergeState::Clean,
+        repo_view::MergeStateStatus::BEHIND => MergeState::Behind,
+        repo_view::MergeStateStatus::BLOCKED => MergeState::Blocked,
+        repo_view::MergeStateStatus::UNSTABLE => MergeState::Unstable,
+        repo_view::MergeStateStatus::HAS_HOOKS => MergeState::HasHooks,
+        repo_view::MergeStateStatus::DIRTY => MergeState::Conflicting,
+        repo_view::MergeStateStatus::DRAFT
+        | repo_view::MergeStateStatus::UNKNOWN
+        | repo_view::MergeStateStatus::Other(_) => MergeState::Unknown,
     }
 }
 
-fn is_empty(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest) -> bool {
-    pr.additions == 0 && pr.deletions == 0
-}
-
-fn has_conflicts(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest) -> bool {
-    matches!(pr.mergeable, repo_view::MergeableState::CONFLICTING)
-}
-
-fn pr_files(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest) -> Vec<String> {
-    match &pr.files {
-        Some(files) => files
-            .nodes
-            .iter()
-            .flatten()
-            .flatten()
-            .map(|f| f.path.clone())
-            .collect(),
-        None => vec![],
+async fn pr_stats(
+    context: &Context<'_>,
+    pr: repo_view::RepoViewSearchEdgesNodeOnPullRequest,
+) -> Result<Option<Pr>> {
+    let Context {
+        github_api_token,
+        username,
+        options,
+        teams,
+        filters,
+        rules,
+        blames,
+        progress,
+    } = *context;
+
+    // The same pull request can be returned by more than one search
+    if !filters.first_seen(&pr.url) {
+        return Ok(None);
+    }
+    // The filters run after every stage, to skip the queries of the next ones
+    let searched = searched_pr(&pr, username);
+    if !filters.keep(&searched, Stage::Search) {
+        return Ok(None);
     }
-}
 
-fn pr_labels(labels: Option<&repo_view::RepoViewSearchEdgesNodeOnPullRequestLabels>) -> Labels {
-    match labels {
-        Some(labels) => Labels(
-            labels
-                .nodes
-                .iter()
-                .flatten()
-                .flatten()
-                .map(|l| Label {
-                    name: l.name.clone(),
-                    color: l.color.clone(),
-                })
-                .collect(),
+    let branch_rules = rules
+        .branch(
+            github_api_token,
+            &pr.repository.owner.login,
+            &pr.repository.name,
+            &pr.base_ref_name,
+        )
+        .await?;
+    let LastCommit {
+        pushed_date: last_commit_pushed_date,
+        tests_result,
+        all_tests_result,
+        failed_checks,
+    } = last_commit(
+        github_api_token,
+        &pr,
+        &branch_rules,
+        options.tests_regex.as_ref(),
+        options.all_checks,
+    )
+    .await?;
+    let committed = Pr {
+        last_commit_pushed_date,
+        last_commit_age_min: age(last_commit_pushed_date),
+        tests_result,
+        all_tests_result,
+        failed_checks,
+        required_approvals: required_approvals(
+            protected_approvals(&pr, &branch_rules),
+            &searched.repo,
+            options,
         ),
-        None => Labels(vec![]),
+        ..searched
+    };
+    if !filters.keep(&committed, Stage::Commit) {
+        return Ok(None);
     }
-}
 
-async fn pr_stats(
-    github_api_token: &str,
-    username: &str,
-    options: &PrArgs,
-    pr: repo_view::RepoViewSearchEdgesNodeOnPullRequest,
-) -> Option<Pr> {
-    let (last_commit_pushed_date, tests_result) = last_commit(&pr, options.tests_regex.as_ref());
-
-    if !include_by_tests_state(&tests_result, options) {
-        return None;
+    let Connections {
+        review_threads,
+        reviews,
+        labels,
+        review_requests,
+    } = connections::complete(github_api_token, &pr).await?;
+    let reviewed_by_me = !review_states(&reviews, username, true).is_empty();
+    let reviews = review_states(&reviews, &committed.author, false);
+    let (review_requested, team) = review_requested(&review_requests, username, teams);
+    let connected = Pr {
+        open_conversations: pr_open_conversations(&review_threads),
+        num_approvals: pr_num_approvals(&reviews),
+        num_reviewers: pr_num_reviewers(&reviews),
+        labels: Labels(labels),
+        requested: matches!(review_requested, ReviewRequested::RequestedNotAsCodeOwner),
+        codeowner: matches!(review_requested, ReviewRequested::RequestedAsCodeOwner),
+        team,
+        reviewed_by_me,
+        ..committed
+    };
+    if !filters.keep(&connected, Stage::Connections) {
+        return Ok(None);
     }
 
     let (files, blame) = if options.blame {
-        let files = pr_files(&pr);
+        let files = connections::all_files(github_api_token, &pr).await?;
         let blame = blame::blame(
             github_api_token,
             &pr.repository.name,
             &pr.repository.owner.login,
-            &files,
+            connections::blame_files(&files),
             username,
+            blames,
+            progress,
         )
-        .await;
+        .await?;
         (Files(files), blame)
     } else {
         (Files(vec![]), false)
     };
+    let pr = Pr {
+        files,
+        blame,
+        ..connected
+    };
 
-    let author = author(&pr);
-    let reviews = review_states(pr.reviews.as_ref(), &author, false);
-    let review_requested = review_requested(pr.review_requests.as_ref(), username);
+    Ok(filters.keep(&pr, Stage::Blame).then_some(pr))
+}
 
-    Some(Pr {
+/// The pull request with the fields of the search results. The other fields have their
+/// initial values, and reviewed_by_me is computed from the first page of reviews.
+fn searched_pr(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest, username: &str) -> Pr {
+    let (first_reviews, _) = connections::first_reviews(pr);
+    Pr {
         title: pr.title.clone(),
         url: pr.url.clone(),
-        last_commit_pushed_date,
-        last_commit_age_min: age(last_commit_pushed_date),
-        tests_result,
-        open_conversations: pr_open_conversations(&pr.review_threads),
-        num_approvals: pr_num_approvals(&reviews),
-        num_reviewers: pr_num_reviewers(&reviews),
+        repo: repo(pr),
+        author: author(pr),
+        bot: is_bot(pr),
+        created_at: parse_date(Some(&pr.created_at)),
+        last_commit_pushed_date: None,
+        last_commit_age_min: None,
+        tests_result: TestsState::None,
+        all_tests_result: TestsState::None,
+        failed_checks: vec![],
+        open_conversations: 0,
+        num_approvals: 0,
+        num_reviewers: 0,
+        required_approvals: 0,
         additions: pr.additions,
         deletions: pr.deletions,
+        changed_files: pr.changed_files,
         based_on_main_branch: pr_based_on_main_branch(&pr.base_ref_name),
-        files,
-        blame,
-        labels: pr_labels(pr.labels.as_ref()),
-        requested: matches!(review_requested, ReviewRequested::RequestedNotAsCodeOwner),
-        codeowner: matches!(review_requested, ReviewRequested::RequestedAsCodeOwner),
-    })
+        merge_state: merge_state(&pr.mergeable, &pr.merge_state_status),
+        files: Files(vec![]),
+        blame: false,
+        labels: Labels(vec![]),
+        requested: false,
+        codeowner: false,
+        team: None,
+        reviewed_by_me: !review_states(&first_reviews, username, true).is_empty(),
+    }
+}
+
+/// The number of approvals required by the protection rules and the rulesets of the base branch, the strictest
+fn protected_approvals(
+    pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest,
+    branch_rules: &rules::BranchRules,
+) -> Option<u8> {
+    pr.base_ref
+        .as_ref()
+        .and_then(|base_ref| base_ref.ref_update_rule.as_ref())
+        .and_then(|rule| rule.required_approving_review_count)
+        .and_then(|count| u8::try_from(count).ok())
+        .max(branch_rules.required_approvals)
+}
+
+/// The approvals required by the base branch, falling back to the per-repository
+/// values (--repo-approvals and ATEAM_REPO_APPROVALS) and to --required-approvals
+fn required_approvals(protected: Option<u8>, repo: &str, options: &PrArgs) -> u8 {
+    protected
+        .or_else(|| {
+            options
+                .repo_approvals
+                .iter()
+                .find(|repo_approvals| repo_approvals.repo.eq_ignore_ascii_case(repo))
+                .map(|repo_approvals| repo_approvals.approvals)
+        })
+        .unwrap_or(options.required_approvals)
+}
+
+/// The repository of the pull request, as OWNER/NAME
+fn repo(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest) -> String {
+    format!("{}/{}", pr.repository.owner.login, pr.repository.name)
 }
 
 fn author(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest) -> String {
@@ -449,164 +674,246 @@ fn author(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest) -> String {
     }
 }
 
-fn include_by_tests_state(state: &TestsState, options: &PrArgs) -> bool {
-    match state {
-        TestsState::Success => !options.exclude_tests_success,
-        TestsState::Failure => options.include_tests_failure,
-        TestsState::Pending => options.include_tests_pending,
-        TestsState::None => !options.exclude_tests_none,
-    }
+fn is_bot(pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest) -> bool {
+    matches!(
+        &pr.author,
+        Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestAuthor {
+            on: repo_view::RepoViewSearchEdgesNodeOnPullRequestAuthorOn::Bot,
+            ..
+        })
+    )
 }
 
-fn last_commit(
+/// State of the last commit of a pull request
+struct LastCommit {
+    pushed_date: Option<DT<Utc>>,
+    tests_result: TestsState,
+    all_tests_result: TestsState,
+    failed_checks: Vec<String>,
+}
+
+async fn last_commit(
+    github_api_token: &str,
     pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest,
+    branch_rules: &rules::BranchRules,
     tests_regex: Option<&String>,
-) -> (Option<DT<Utc>>, TestsState) {
-    let tests_re = regex(tests_regex);
-    if let Some((pushed_date, state)) = pr
+    all_checks: bool,
+) -> Result<LastCommit> {
+    let tests_re = tests_regex
+        .map(|text| Regex::new(text).map_err(|e| anyhow!("Invalid --tests-regex {text:?}: {e}")))
+        .transpose()?;
+    let required = if all_checks {
+        vec![]
+    } else {
+        required_checks(pr, branch_rules)
+    };
+    match pr
         .commits
         .nodes
         .as_ref()
         .and_then(|nodes| nodes[0].as_ref())
-        .map(|node| {
-            (
-                &node.commit.pushed_date,
-                node.commit
-                    .status_check_rollup
-                    .as_ref()
-                    .map(|status| commit_status_state(status, tests_re.as_ref())),
-            )
-        })
     {
-        (
-            parse_date(pushed_date.as_ref()),
-            state.unwrap_or(TestsState::None),
-        )
-    } else {
-        (None, TestsState::None)
+        Some(node) => {
+            let pushed_date = parse_date(node.commit.pushed_date.as_ref());
+            match node.commit.status_check_rollup.as_ref() {
+                Some(status) => {
+                    let all_tests_result = checks::tests_state(&status.state);
+                    let (tests_result, failed_checks) = match tests_re {
+                        Some(tests_re) => commit_tests_state_from_contexts(
+                            &commit_checks(github_api_token, pr, status).await?,
+                            &tests_re,
+                        ),
+                        None if !required.is_empty() => required_tests_state(
+                            &commit_checks(github_api_token, pr, status).await?,
+                            &required,
+                        ),
+                        None => (all_tests_result.clone(), vec![]),
+                    };
+                    Ok(LastCommit {
+                        pushed_date,
+                        tests_result,
+                        all_tests_result,
+                        failed_checks,
+                    })
+                }
+                None => Ok(LastCommit {
+                    pushed_date,
+                    tests_result: if tests_re.is_none() && !required.is_empty() {
+                        TestsState::Missing
+                    } else {
+                        TestsState::None
+                    },
+                    all_tests_result: TestsState::None,
+                    failed_checks: vec![],
+                }),
+            }
+        }
+        None => Ok(LastCommit {
+            pushed_date: None,
+            tests_result: TestsState::None,
+            all_tests_result: TestsState::None,
+            failed_checks: vec![],
+        }),
     }
 }
 
-fn commit_status_state(
+/// The names of the status checks required by the protection rules and the rulesets of the base branch
+fn required_checks(
+    pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest,
+    branch_rules: &rules::BranchRules,
+) -> Vec<String> {
+    pr.base_ref
+        .as_ref()
+        .and_then(|base_ref| base_ref.ref_update_rule.as_ref())
+        .and_then(|rule| rule.required_status_check_contexts.as_ref())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .chain(&branch_rules.required_checks)
+        .unique()
+        .cloned()
+        .collect()
+}
+
+async fn commit_checks(
+    github_api_token: &str,
+    pr: &repo_view::RepoViewSearchEdgesNodeOnPullRequest,
     status: &repo_view::RepoViewSearchEdgesNodeOnPullRequestCommitsNodesCommitStatusCheckRollup,
-    tests_re: Option<&Regex>,
-) -> TestsState {
-    match tests_re {
-        Some(tests_re) => {
-            commit_tests_state_from_contexts(status.contexts.nodes.as_ref(), tests_re)
-        }
-        None => tests_state(&status.state),
+) -> Result<Vec<Check>> {
+    let mut checks: Vec<Check> = status
+        .contexts
+        .nodes
+        .iter()
+        .flatten()
+        .flatten()
+        .map(check)
+        .collect();
+    let page_info = &status.contexts.page_info;
+    // The search doesn't fetch the checks without --tests-regex
+    if page_info.has_next_page || checks.is_empty() {
+        checks.append(
+            &mut checks::remaining_checks(github_api_token, &pr.id, page_info.end_cursor.clone())
+                .await?,
+        );
     }
+    Ok(checks)
 }
 
+fn check(
+    node: &repo_view::RepoViewSearchEdgesNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes,
+) -> Check {
+    match node {
+        repo_view::RepoViewSearchEdgesNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes::StatusContext(status_context) => Check {
+            name: status_context.context.clone(),
+            state: checks::tests_state(&status_context.state),
+        },
+        repo_view::RepoViewSearchEdgesNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes::CheckRun(check_run) => Check {
+            name: check_run.name.clone(),
+            state: checks::check_run_state(&check_run.status, check_run.conclusion.as_ref()),
+        },
+    }
+}
+
+/// Combine the states of the checks matching the regex. It returns the names of the failed ones too.
 fn commit_tests_state_from_contexts(
-    contexts_nodes: Option<&Vec<Option<repo_view::RepoViewSearchEdgesNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes>>>,
+    checks: &[Check],
     tests_re: &Regex,
-) -> TestsState {
-    match contexts_nodes {
-        Some(nodes) => {
-            let states: Vec<TestsState> = nodes.iter().filter_map(|node|
-                match node {
-                  Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestCommitsNodesCommitStatusCheckRollupContextsNodes::StatusContext(status_context)) => {
-                      if tests_re.is_match(&status_context.context) {
-                          Some(tests_state(&status_context.state))
-                      } else {
-                          None
-                      }
-                  },
-                  _ => None
-              }).collect();
-            match states {
-                v if v.iter().any(|state| matches!(state, TestsState::Failure)) => {
-                    TestsState::Failure
-                }
-                v if v.iter().any(|state| matches!(state, TestsState::Pending)) => {
-                    TestsState::Pending
-                }
-                v if v.iter().all(|state| matches!(state, TestsState::Success)) => {
-                    TestsState::Success
-                }
-                _ => TestsState::None,
-            }
-        }
-        None => TestsState::None,
-    }
+) -> (TestsState, Vec<String>) {
+    let matched: Vec<&Check> = checks
+        .iter()
+        .filter(|check| tests_re.is_match(&check.name))
+        .collect();
+    combined_tests_state(&matched)
 }
 
-fn tests_state(state: &repo_view::StatusState) -> TestsState {
-    match state {
-        repo_view::StatusState::SUCCESS => TestsState::Success,
-        repo_view::StatusState::PENDING | repo_view::StatusState::EXPECTED => TestsState::Pending,
-        repo_view::StatusState::FAILURE | repo_view::StatusState::ERROR => TestsState::Failure,
-        repo_view::StatusState::Other(_) => TestsState::None,
+/// Combine the states of the required checks. A required check that did not report any status
+/// makes the state Missing, unless another required check failed.
+fn required_tests_state(checks: &[Check], required: &[String]) -> (TestsState, Vec<String>) {
+    let matched: Vec<&Check> = checks
+        .iter()
+        .filter(|check| required.contains(&check.name))
+        .collect();
+    let missing = required
+        .iter()
+        .any(|name| !checks.iter().any(|check| &check.name == name));
+    match combined_tests_state(&matched) {
+        (TestsState::Failure, failed) => (TestsState::Failure, failed),
+        (_, failed) if missing => (TestsState::Missing, failed),
+        result => result,
     }
 }
 
-fn pr_open_conversations(
-    review_threads: &repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewThreads,
-) -> i64 {
-    #[allow(clippy::cast_possible_wrap, clippy::map_unwrap_or)]
-    review_threads.nodes.as_ref().map_or(0, |nodes| {
-        nodes
+fn combined_tests_state(checks: &[&Check]) -> (TestsState, Vec<String>) {
+    let failed: Vec<String> = checks
+        .iter()
+        .filter(|check| matches!(check.state, TestsState::Failure))
+        .map(|check| check.name.clone())
+        .collect();
+    let state = match checks {
+        [] => TestsState::None,
+        _ if !failed.is_empty() => TestsState::Failure,
+        v if v
             .iter()
-            .filter(|review_thread| {
-                review_thread.as_ref().is_some_and(|review_thread| {
-                    !review_thread.is_resolved && !review_thread.is_outdated
-                })
-            })
-            .count() as i64
-    })
+            .any(|check| matches!(check.state, TestsState::Pending)) =>
+        {
+            TestsState::Pending
+        }
+        v if v
+            .iter()
+            .all(|check| matches!(check.state, TestsState::Success)) =>
+        {
+            TestsState::Success
+        }
+        _ => TestsState::None,
+    };
+    (state, failed)
+}
+
+fn pr_open_conversations(review_threads: &[ReviewThread]) -> i64 {
+    #[allow(clippy::cast_possible_wrap)]
+    let count = review_threads
+        .iter()
+        .filter(|review_thread| !review_thread.is_resolved && !review_thread.is_outdated)
+        .count() as i64;
+    count
 }
 
 fn review_states<'a>(
-    reviews: Option<&'a repo_view::RepoViewSearchEdgesNodeOnPullRequestReviews>,
+    reviews: &'a [PrReview],
     author: &str,
     reviewed_by_author: bool,
-) -> Vec<&'a repo_view::PullRequestReviewState> {
-    if let Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestReviews {
-        total_count: _,
-        nodes: Some(nodes),
-    }) = reviews
-    {
-        nodes
-            .iter()
-            .filter_map(|review| match review {
-                Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewsNodes {
-                    author:
-                        Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewsNodesAuthor {
-                            login,
-                            on: _,
-                        }),
-                    state,
-                }) => Some((login, state)),
-                _ => None,
-            })
-            .rev() // reverse order: from the newest to the oldest
-            .filter(|review| {
-                if reviewed_by_author {
-                    review.0 == author // include only reviews given by the author of the PR
-                } else {
-                    review.0 != author // exclude reviews given by the author of the PR
-                }
-            })
-            .unique_by(|review| review.0) // unique by user
-            .map(|review| review.1) // take only the state
-            .collect()
-    } else {
-        vec![]
-    }
+) -> Vec<&'a PullRequestReviewState> {
+    reviews
+        .iter()
+        .filter_map(|review| {
+            review
+                .author
+                .as_ref()
+                .map(|login| (login.as_str(), &review.state))
+        })
+        .rev() // reverse order: from the newest to the oldest
+        .filter(|review| {
+            if reviewed_by_author {
+                review.0 == author // include only reviews given by the author of the PR
+            } else {
+                review.0 != author // exclude reviews given by the author of the PR
+            }
+        })
+        .unique_by(|review| review.0) // unique by user
+        .map(|review| review.1) // take only the state
+        .collect()
 }
 
 #[allow(clippy::cast_possible_wrap)]
-fn pr_num_approvals(review_states: &[&repo_view::PullRequestReviewState]) -> i64 {
+fn pr_num_approvals(review_states: &[&PullRequestReviewState]) -> i64 {
     review_states
         .iter()
-        .filter(|&&state| matches!(state, &repo_view::PullRequestReviewState::APPROVED))
+        .filter(|&&state| matches!(state, &PullRequestReviewState::APPROVED))
         .count() as i64
 }
 
 #[allow(clippy::cast_possible_wrap)]
-fn pr_num_reviewers(review_states: &[&repo_view::PullRequestReviewState]) -> i64 {
+fn pr_num_reviewers(review_states: &[&PullRequestReviewState]) -> i64 {
     review_states.len() as i64
 }
 
@@ -622,38 +929,45 @@ fn pr_based_on_main_branch(base_branch_name: &str) -> bool {
     base_branch_name == "main" || base_branch_name == "master"
 }
 
+/// How the user has been requested to review the pull request, and the slug of their team
+/// when the request came from a team. Direct requests take precedence over team requests.
 fn review_requested(
-    requests: Option<&repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewRequests>,
+    requests: &[ReviewRequest],
     username: &str,
-) -> ReviewRequested {
-    match requests {
-        Some(requests) => {
-            requests.nodes.iter().flatten().flatten().find(|r|
-            match &r.requested_reviewer {
-                Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewRequestsNodesRequestedReviewer::User(reviewer)) =>
-reviewer.login == username,
-                Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewRequestsNodesRequestedReviewer::Team(team)) =>
-                    team.members.nodes.iter().flatten().flatten().any(|member| member.login == username),
-                Some(repo_view::RepoViewSearchEdgesNodeOnPullRequestReviewRequestsNodesRequestedReviewer::Mannequin) => false, // Just ignore Mannequins
-                None => panic!("Something is wrong with the GitHub token! Have you added the read:org scope?"),
-            })
-            .map_or(
-                ReviewRequested::NotRequested,
-                |r|
-                 if r.as_code_owner {
-                       ReviewRequested::RequestedAsCodeOwner
-                 } else {
-                       ReviewRequested::RequestedNotAsCodeOwner
-                 }
-            )
-        }
-        None => ReviewRequested::NotRequested,
+    teams: &[Team],
+) -> (ReviewRequested, Option<String>) {
+    let is_for_user = |r: &&ReviewRequest| match &r.reviewer {
+        Some(Reviewer::User(login)) => login == username,
+        // Just ignore Mannequins. There is no reviewer for deleted users and teams, or for the
+        // teams the token can't read without the read:org scope.
+        Some(Reviewer::Team(_) | Reviewer::Mannequin) | None => false,
+    };
+    let is_for_my_team = |r: &&ReviewRequest| matches!(&r.reviewer, Some(Reviewer::Team(team)) if teams.contains(team));
+
+    match requests
+        .iter()
+        .find(is_for_user)
+        .or_else(|| requests.iter().find(is_for_my_team))
+    {
+        Some(r) => (
+            if r.as_code_owner {
+                ReviewRequested::RequestedAsCodeOwner
+            } else {
+                ReviewRequested::RequestedNotAsCodeOwner
+            },
+            match &r.reviewer {
+                Some(Reviewer::Team(team)) => Some(team.slug.clone()),
+                _ => None,
+            },
+        ),
+        None => (ReviewRequested::NotRequested, None),
     }
 }
 
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::super::types::test_pr;
     use super::*;
 
     // test limited_batch_size
@@ -664,6 +978,27 @@ mod tests {
         assert_eq!(limited_batch_size(101), 100);
     }
 
+    #[test]
+    fn test_merge_state() {
+        use repo_view::{MergeStateStatus, MergeableState};
+        assert!(matches!(
+            merge_state(&MergeableState::CONFLICTING, &MergeStateStatus::DIRTY),
+            MergeState::Conflicting
+        ));
+        assert!(matches!(
+            merge_state(&MergeableState::UNKNOWN, &MergeStateStatus::BLOCKED),
+            MergeState::Unknown
+        ));
+        assert!(matches!(
+            merge_state(&MergeableState::MERGEABLE, &MergeStateStatus::BEHIND),
+            MergeState::Behind
+        ));
+        assert!(matches!(
+            merge_state(&MergeableState::MERGEABLE, &MergeStateStatus::CLEAN),
+            MergeState::Clean
+        ));
+    }
+
     // test pr_based_on_main_branch
     #[test]
     fn test_pr_based_on_main_branch() {
@@ -685,20 +1020,8 @@ mod tests {
         let pr = Pr {
             title: "test".to_string(),
             url: "https://example.com/1".to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
-            num_approvals: 0,
-            num_reviewers: 0,
             additions: 0,
-            deletions: 0,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            ..test_pr()
         };
         let scored_pr = ScoredPr {
             pr: pr.clone(),
@@ -716,20 +1039,9 @@ mod tests {
         let pr_high = Pr {
             title: "High score PR".to_string(),
             url: "https://example.com/2".to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
             num_approvals: 5, // More approvals
-            num_reviewers: 0,
             additions: 0,
-            deletions: 0,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            ..test_pr()
         };
         let score_high = Score::from_pr(1, &pr_high);
         let scored_pr_high = ScoredPr {
@@ -741,20 +1053,9 @@ mod tests {
         let pr_low = Pr {
             title: "Low score PR".to_string(),
             url: "https://example.com/1".to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
             num_approvals: 1, // Fewer approvals
-            num_reviewers: 0,
             additions: 0,
-            deletions: 0,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            ..test_pr()
         };
         let score_low = Score::from_pr(1, &pr_low);
         let scored_pr_low = ScoredPr {
@@ -781,20 +1082,10 @@ mod tests {
         let pr1 = Pr {
             title: "First PR".to_string(),
             url: "https://example.com/1".to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
             num_approvals: 2,
-            num_reviewers: 0,
             additions: 100,
             deletions: 50,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            ..test_pr()
         };
         let score1 = Score::from_pr(1, &pr1);
         let scored_pr1 = ScoredPr {
@@ -805,20 +1096,10 @@ mod tests {
         let pr2 = Pr {
             title: "Second PR".to_string(),
             url: "https://example.com/2".to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
             num_approvals: 2,
-            num_reviewers: 0,
             additions: 100,
             deletions: 50,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            ..test_pr()
         };
         let score2 = Score::from_pr(1, &pr2);
         let scored_pr2 = ScoredPr {
@@ -844,20 +1125,9 @@ mod tests {
         let pr1 = Pr {
             title: "Many additions".to_string(),
             url: "https://example.com/1".to_string(),
-            last_commit_pushed_date: None,
             last_commit_age_min: Some(60), // 1 hour old
-            tests_result: TestsState::Success,
-            open_conversations: 0,
-            num_approvals: 0,
-            num_reviewers: 0,
-            additions: 1000, // High additions
-            deletions: 0,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            additions: 1000,               // High additions
+            ..test_pr()
         };
         let score1 = Score::from_pr(1, &pr1);
         prs.push(ScoredPr {
@@ -869,20 +1139,9 @@ mod tests {
         let pr2 = Pr {
             title: "Old PR".to_string(),
             url: "https://example.com/2".to_string(),
-            last_commit_pushed_date: None,
             last_commit_age_min: Some(1440), // 24 hours old
-            tests_result: TestsState::Success,
-            open_conversations: 0,
-            num_approvals: 0,
-            num_reviewers: 0,
-            additions: 100, // Low additions
-            deletions: 0,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            additions: 100,                  // Low additions
+            ..test_pr()
         };
         let score2 = Score::from_pr(1, &pr2);
         prs.push(ScoredPr {
@@ -894,20 +1153,10 @@ mod tests {
         let pr3 = Pr {
             title: "Main branch PR".to_string(),
             url: "https://example.com/3".to_string(),
-            last_commit_pushed_date: None,
             last_commit_age_min: Some(60), // 1 hour old
-            tests_result: TestsState::Success,
-            open_conversations: 0,
-            num_approvals: 0,
-            num_reviewers: 0,
-            additions: 500, // Medium additions
-            deletions: 0,
-            based_on_main_branch: true, // Bonus for main branch
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            additions: 500,                // Medium additions
+            based_on_main_branch: true,    // Bonus for main branch
+            ..test_pr()
         };
         let score3 = Score::from_pr(1, &pr3);
         prs.push(ScoredPr {
@@ -949,6 +1198,281 @@ mod tests {
         assert!(!pr_based_on_main_branch("hotfix/fix-bug"));
     }
 
+    // Tests for commit_tests_state_from_contexts
+    fn make_check(name: &str, state: TestsState) -> Check {
+        Check {
+            name: name.to_string(),
+            state,
+        }
+    }
+
+    #[test]
+    fn test_commit_tests_state_from_contexts_no_match() {
+        let checks = vec![make_check("lint", TestsState::Failure)];
+        let re = Regex::new("^test").unwrap();
+        let (state, failed) = commit_tests_state_from_contexts(&checks, &re);
+        assert!(matches!(state, TestsState::None));
+        assert!(failed.is_empty());
+    }
+
+    #[test]
+    fn test_commit_tests_state_from_contexts_failed_checks() {
+        let checks = vec![
+            make_check("test-unit", TestsState::Success),
+            make_check("test-e2e", TestsState::Failure),
+            make_check("test-integration", TestsState::Pending),
+            make_check("lint", TestsState::Failure),
+        ];
+        let re = Regex::new("^test").unwrap();
+        let (state, failed) = commit_tests_state_from_contexts(&checks, &re);
+        assert!(matches!(state, TestsState::Failure));
+        assert_eq!(failed, vec!["test-e2e".to_string()]);
+    }
+
+    #[test]
+    fn test_commit_tests_state_from_contexts_pending_and_success() {
+        let re = Regex::new("^test").unwrap();
+        let pending = vec![
+            make_check("test-unit", TestsState::Success),
+            make_check("test-e2e", TestsState::Pending),
+        ];
+        assert!(matches!(
+            commit_tests_state_from_contexts(&pending, &re).0,
+            TestsState::Pending
+        ));
+        let success = vec![make_check("test-unit", TestsState::Success)];
+        assert!(matches!(
+            commit_tests_state_from_contexts(&success, &re).0,
+            TestsState::Success
+        ));
+    }
+
+    // Tests for required_tests_state
+    #[test]
+    fn test_required_tests_state_ignores_optional_checks() {
+        let checks = vec![
+            make_check("build", TestsState::Success),
+            make_check("flaky", TestsState::Failure),
+        ];
+        let (state, failed) = required_tests_state(&checks, &["build".to_string()]);
+        assert!(matches!(state, TestsState::Success));
+        assert!(failed.is_empty());
+    }
+
+    #[test]
+    fn test_required_tests_state_missing() {
+        let checks = vec![make_check("build", TestsState::Success)];
+        let (state, _) =
+            required_tests_state(&checks, &["build".to_string(), "deploy".to_string()]);
+        assert!(matches!(state, TestsState::Missing));
+    }
+
+    #[test]
+    fn test_required_tests_state_failure_wins_over_missing() {
+        let checks = vec![make_check("build", TestsState::Failure)];
+        let (state, failed) =
+            required_tests_state(&checks, &["build".to_string(), "deploy".to_string()]);
+        assert!(matches!(state, TestsState::Failure));
+        assert_eq!(failed, vec!["build".to_string()]);
+    }
+
+    // Tests for the pull request connections
+    fn make_review(author: &str, state: PullRequestReviewState) -> PrReview {
+        PrReview {
+            author: Some(author.to_string()),
+            state,
+        }
+    }
+
+    #[test]
+    fn test_review_states_latest_per_reviewer() {
+        let reviews = vec![
+            make_review("alice", PullRequestReviewState::CHANGES_REQUESTED),
+            make_review("bob", PullRequestReviewState::COMMENTED),
+            make_review("alice", PullRequestReviewState::APPROVED),
+            make_review("author", PullRequestReviewState::COMMENTED),
+        ];
+        let states = review_states(&reviews, "author", false);
+        assert_eq!(states.len(), 2);
+        assert_eq!(pr_num_approvals(&states), 1);
+        assert_eq!(review_states(&reviews, "author", true).len(), 1);
+    }
+
+    #[test]
+    fn test_pr_open_conversations() {
+        let threads = vec![
+            ReviewThread {
+                is_resolved: false,
+                is_outdated: false,
+            },
+            ReviewThread {
+                is_resolved: true,
+                is_outdated: false,
+            },
+            ReviewThread {
+                is_resolved: false,
+                is_outdated: true,
+            },
+        ];
+        assert_eq!(pr_open_conversations(&threads), 1);
+    }
+
+    fn make_team(org: &str, slug: &str) -> Team {
+        Team {
+            org: org.to_string(),
+            slug: slug.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_review_requested_by_team() {
+        let requests = vec![
+            ReviewRequest {
+                as_code_owner: false,
+                reviewer: Some(Reviewer::User("alice".to_string())),
+            },
+            ReviewRequest {
+                as_code_owner: true,
+                reviewer: Some(Reviewer::Team(make_team("acme", "backend"))),
+            },
+        ];
+        let carol_teams = vec![make_team("acme", "backend")];
+        let (requested, team) = review_requested(&requests, "carol", &carol_teams);
+        assert!(matches!(requested, ReviewRequested::RequestedAsCodeOwner));
+        assert_eq!(team, Some("backend".to_string()));
+
+        let (requested, team) = review_requested(&requests, "alice", &carol_teams);
+        assert!(matches!(
+            requested,
+            ReviewRequested::RequestedNotAsCodeOwner
+        ));
+        assert_eq!(team, None);
+
+        let dave_teams = vec![make_team("other", "backend")];
+        let (requested, team) = review_requested(&requests, "dave", &dave_teams);
+        assert!(matches!(requested, ReviewRequested::NotRequested));
+        assert_eq!(team, None);
+    }
+
+    #[test]
+    fn test_review_requested_deleted_reviewer() {
+        let requests = vec![
+            ReviewRequest {
+                as_code_owner: false,
+                reviewer: None,
+            },
+            ReviewRequest {
+                as_code_owner: false,
+                reviewer: Some(Reviewer::User("alice".to_string())),
+            },
+        ];
+        let (requested, team) = review_requested(&requests, "alice", &[]);
+        assert!(matches!(
+            requested,
+            ReviewRequested::RequestedNotAsCodeOwner
+        ));
+        assert_eq!(team, None);
+
+        let (requested, _) = review_requested(&requests, "bob", &[]);
+        assert!(matches!(requested, ReviewRequested::NotRequested));
+    }
+
+    // Tests for github_query
+    fn make_options(args: &[&str]) -> PrArgs {
+        use clap::Parser;
+        let mut argv = vec!["ateam", "pr"];
+        argv.extend_from_slice(args);
+        match super::super::cli::Ateam::parse_from(argv).cmd {
+            super::super::cli::Command::Pr(options) => options,
+            super::super::cli::Command::Followup(_) | super::super::cli::Command::Tui(_) => {
+                unreachable!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_required_approvals() {
+        let options = make_options(&[
+            "--required-approvals",
+            "3",
+            "--repo-approvals",
+            "Acme/Api=1,acme/web=0",
+        ]);
+        assert_eq!(required_approvals(Some(2), "acme/api", &options), 2);
+        assert_eq!(required_approvals(None, "acme/api", &options), 1);
+        assert_eq!(required_approvals(None, "ACME/WEB", &options), 0);
+        assert_eq!(required_approvals(None, "acme/cli", &options), 3);
+        assert_eq!(required_approvals(None, "acme/cli", &make_options(&[])), 2);
+    }
+
+    #[test]
+    fn test_github_query_any_query() {
+        let options = make_options(&[
+            "--query",
+            "urgent",
+            "--any-query",
+            "review-requested:@me",
+            "--any-query",
+            "label:urgent",
+        ]);
+        let queries: Vec<String> = any_queries(&options)
+            .into_iter()
+            .map(|any_query| github_query("me", &options, &options.repo, any_query).to_string())
+            .collect();
+        assert_eq!(
+            queries,
+            vec![
+                "is:pr is:open draft:false urgent review-requested:@me",
+                "is:pr is:open draft:false urgent label:urgent",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_github_query_without_any_query() {
+        let options = make_options(&["--repo", "frisoft/ateam"]);
+        let queries: Vec<String> = any_queries(&options)
+            .into_iter()
+            .map(|any_query| github_query("me", &options, &options.repo, any_query).to_string())
+            .collect();
+        assert_eq!(
+            queries,
+            vec!["is:pr is:open draft:false repo:frisoft/ateam"]
+        );
+    }
+
+    #[test]
+    fn test_github_query_scopes() {
+        let options = make_options(&[
+            "--involves-me",
+            "--mentions-me",
+            "--commented-by-me",
+            "--created-before",
+            "2024-01-31",
+            "--base",
+            "main",
+        ]);
+        assert_eq!(
+            github_query("me", &options, &options.repo, None).to_string(),
+            "is:pr is:open draft:false involves:me mentions:me commenter:me created:<2024-01-31T00:00:00Z base:main"
+        );
+    }
+
+    #[test]
+    fn test_github_query_escapes_labels() {
+        let options = make_options(&[
+            "--label",
+            "needs review",
+            "--exclude-label",
+            "say \"hi\"",
+            "--only-mine",
+        ]);
+        assert_eq!(
+            github_query("me", &options, &options.repo, None).to_string(),
+            "is:pr is:open draft:false author:me label:\"needs review\" -label:\"say \\\"hi\\\"\""
+        );
+    }
+
     // Tests for limited_batch_size - extended
     #[test]
     fn test_limited_batch_size_zero() {
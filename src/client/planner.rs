@@ -0,0 +1,231 @@
+use std::fmt::Write;
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, SubsecRound, TimeZone, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use graphql_client::{GraphQLQuery, Response};
+
+use super::query::{DateRange, SearchQuery};
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/search_count.graphql",
+    response_derives = "Debug"
+)]
+pub struct SearchCount;
+
+/// GitHub returns at most 1000 results for a search
+const SEARCH_LIMIT: i64 = 1000;
+
+/// How many searches run at the same time
+pub const CONCURRENCY: usize = 8;
+
+/// A window of creation times, both ends included
+type Window = (DateTime<Utc>, DateTime<Utc>);
+
+/// Plan the searches needed to get all the results of the query.
+/// When the query has more results than GitHub can return, it is split by repository
+/// (`by_repo`, if there is more than one) and then by windows of creation times
+/// inside the created range of the query.
+pub async fn plan(
+    github_api_token: &str,
+    query: SearchQuery,
+    by_repo: Vec<SearchQuery>,
+) -> Result<Vec<SearchQuery>> {
+    let count = issue_count(github_api_token, &query.to_string()).await?;
+    if count <= SEARCH_LIMIT {
+        return Ok(vec![query]);
+    }
+
+    let queries = if by_repo.len() > 1 {
+        by_repo
+    } else {
+        vec![query]
+    };
+    split_by_created(github_api_token, queries).await
+}
+
+async fn split_by_created(
+    github_api_token: &str,
+    queries: Vec<SearchQuery>,
+) -> Result<Vec<SearchQuery>> {
+    let now = Utc::now();
+    let mut planned = vec![];
+    let mut pending: Vec<(SearchQuery, Option<Window>)> =
+        queries.into_iter().map(|query| (query, None)).collect();
+
+    while !pending.is_empty() {
+        let counted: Vec<(SearchQuery, Option<Window>, i64)> = stream::iter(pending)
+            .map(|(query, window)| async move {
+                let count = issue_count_in(github_api_token, &query, window).await?;
+                Ok::<_, anyhow::Error>((query, window, count))
+            })
+            .buffer_unordered(CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut next = vec![];
+        for (query, window, count) in counted {
+            let full_window = window.unwrap_or_else(|| created_window(query.created_range(), now));
+            if count <= SEARCH_LIMIT {
+                planned.push(windowed(&query, window));
+            } else if let Some((first, second)) = split_window(full_window) {
+                next.push((query.clone(), Some(first)));
+                next.push((query, Some(second)));
+            } else {
+                eprintln!(
+                    "\nMore than {SEARCH_LIMIT} pull requests created at {}: some of them are missing",
+                    full_window.0
+                );
+                planned.push(windowed(&query, window));
+            }
+        }
+        pending = next;
+    }
+
+    Ok(planned)
+}
+
+/// The created range of the query as a window, from the first day of GitHub to now
+/// when it is not limited
+fn created_window(range: Option<DateRange>, now: DateTime<Utc>) -> Window {
+    let first_day = Utc
+        .with_ymd_and_hms(2008, 1, 1, 0, 0, 0)
+        .single()
+        .expect("a valid date");
+    let now = now.trunc_subsecs(0);
+    match range {
+        None => (first_day, now),
+        Some(DateRange::Since(time)) => (time.trunc_subsecs(0), now),
+        // The search ranges have a precision of one second
+        Some(DateRange::Before(time)) => (first_day, time.trunc_subsecs(0) - Duration::seconds(1)),
+        Some(DateRange::Between(from, to)) => (from, to),
+    }
+}
+
+fn split_window((from, to): Window) -> Option<(Window, Window)> {
+    let seconds = (to - from).num_seconds();
+    if seconds < 1 {
+        None
+    } else {
+        let middle = from + Duration::seconds(seconds / 2);
+        Some(((from, middle), (middle + Duration::seconds(1), to)))
+    }
+}
+
+/// The query limited to the window, which replaces its created range
+fn windowed(query: &SearchQuery, window: Option<Window>) -> SearchQuery {
+    match window {
+        Some((from, to)) => query.clone().created(DateRange::Between(from, to)),
+        None => query.clone(),
+    }
+}
+
+async fn issue_count_in(
+    github_api_token: &str,
+    query: &SearchQuery,
+    window: Option<Window>,
+) -> Result<i64> {
+    issue_count(github_api_token, &windowed(query, window).to_string()).await
+}
+
+async fn issue_count(github_api_token: &str, query: &str) -> Result<i64> {
+    let q = SearchCount::build_query(search_count::Variables {
+        query: query.to_string(),
+    });
+
+    let res = super::call(github_api_token, &q).await?;
+
+    let response_body: Response<search_count::ResponseData> = res.json().await?;
+
+    if let Some(errors) = response_body.errors {
+        let mut error_str = String::new();
+        for error in &errors {
+            write!(error_str, "{error:?}").unwrap();
+        }
+        Err(anyhow!(
+            "Errors counting the results of {query} {error_str}"
+        ))
+    } else {
+        match response_body.data {
+            Some(data) => Ok(data.search.issue_count),
+            None => Err(anyhow!(
+                "Missing response data counting the results of {query}"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_split_window() {
+        assert_eq!(
+            split_window((time("2024-01-01T00:00:00Z"), time("2024-01-11T00:00:00Z"))),
+            Some((
+                (time("2024-01-01T00:00:00Z"), time("2024-01-06T00:00:00Z")),
+                (time("2024-01-06T00:00:01Z"), time("2024-01-11T00:00:00Z"))
+            ))
+        );
+        assert_eq!(
+            split_window((time("2024-01-01T00:00:00Z"), time("2024-01-01T00:00:01Z"))),
+            Some((
+                (time("2024-01-01T00:00:00Z"), time("2024-01-01T00:00:00Z")),
+                (time("2024-01-01T00:00:01Z"), time("2024-01-01T00:00:01Z"))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_split_window_single_second() {
+        let second = time("2024-01-01T00:00:00Z");
+        assert_eq!(split_window((second, second)), None);
+    }
+
+    #[test]
+    fn test_created_window() {
+        let now = time("2024-06-01T12:00:00.5Z");
+        assert_eq!(
+            created_window(None, now),
+            (time("2008-01-01T00:00:00Z"), time("2024-06-01T12:00:00Z"))
+        );
+        assert_eq!(
+            created_window(Some(DateRange::Before(time("2024-03-01T00:00:00Z"))), now),
+            (time("2008-01-01T00:00:00Z"), time("2024-02-29T23:59:59Z"))
+        );
+        assert_eq!(
+            created_window(Some(DateRange::Since(time("2024-03-01T00:00:00Z"))), now),
+            (time("2024-03-01T00:00:00Z"), time("2024-06-01T12:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_windowed() {
+        let query = SearchQuery::open_pull_requests();
+        let window = (time("2024-01-01T00:00:00Z"), time("2024-01-31T23:59:59Z"));
+        assert_eq!(windowed(&query, None).to_string(), "is:pr is:open");
+        assert_eq!(
+            windowed(&query, Some(window)).to_string(),
+            "is:pr is:open created:2024-01-01T00:00:00Z..2024-01-31T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn test_windowed_replaces_created_before() {
+        let query = SearchQuery::open_pull_requests()
+            .created(DateRange::Before(time("2024-03-01T00:00:00Z")));
+        let window = created_window(query.created_range(), Utc::now());
+        let (first, _) = split_window(window).unwrap();
+        assert_eq!(
+            windowed(&query, Some(first)).to_string(),
+            "is:pr is:open created:2008-01-01T00:00:00Z..2016-01-30T23:59:59Z"
+        );
+    }
+}
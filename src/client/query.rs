@@ -0,0 +1,364 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// A GitHub search query built from typed qualifiers.
+/// The values are quoted and escaped when rendered, so they cannot change the meaning of the query.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Qualifier {
+        negated: bool,
+        name: &'static str,
+        value: String,
+    },
+    // A date range, rendered without quotes. A query has at most one created range.
+    Dates {
+        name: &'static str,
+        range: DateRange,
+    },
+    // Text typed by the user, like the --query values. It is used as it is.
+    Raw(String),
+}
+
+/// A range of dates for the created and updated qualifiers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateRange {
+    Since(DateTime<Utc>),
+    Before(DateTime<Utc>),
+    /// Both ends included
+    Between(DateTime<Utc>, DateTime<Utc>),
+}
+
+/// Values of the review qualifier
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum ReviewState {
+    None,
+    Required,
+    Approved,
+    ChangesRequested,
+}
+
+impl SearchQuery {
+    /// The open pull requests
+    pub fn open_pull_requests() -> Self {
+        SearchQuery::default()
+            .qualifier("is", "pr")
+            .qualifier("is", "open")
+    }
+
+    fn qualifier(self, name: &'static str, value: &str) -> Self {
+        self.term(false, name, value)
+    }
+
+    fn not_qualifier(self, name: &'static str, value: &str) -> Self {
+        self.term(true, name, value)
+    }
+
+    fn term(mut self, negated: bool, name: &'static str, value: &str) -> Self {
+        self.terms.push(Term::Qualifier {
+            negated,
+            name,
+            value: value.to_string(),
+        });
+        self
+    }
+
+    pub fn draft(self, draft: bool) -> Self {
+        self.qualifier("draft", if draft { "true" } else { "false" })
+    }
+
+    pub fn repo(self, repo: &str) -> Self {
+        self.qualifier("repo", repo)
+    }
+
+    pub fn org(self, org: &str) -> Self {
+        self.qualifier("org", org)
+    }
+
+    #[allow(dead_code)]
+    pub fn user(self, user: &str) -> Self {
+        self.qualifier("user", user)
+    }
+
+    pub fn label(self, label: &str) -> Self {
+        self.qualifier("label", label)
+    }
+
+    pub fn not_label(self, label: &str) -> Self {
+        self.not_qualifier("label", label)
+    }
+
+    pub fn author(self, author: &str) -> Self {
+        self.qualifier("author", author)
+    }
+
+    pub fn not_author(self, author: &str) -> Self {
+        self.not_qualifier("author", author)
+    }
+
+    pub fn involves(self, user: &str) -> Self {
+        self.qualifier("involves", user)
+    }
+
+    pub fn mentions(self, user: &str) -> Self {
+        self.qualifier("mentions", user)
+    }
+
+    pub fn commenter(self, user: &str) -> Self {
+        self.qualifier("commenter", user)
+    }
+
+    pub fn review_requested(self, user: &str) -> Self {
+        self.qualifier("review-requested", user)
+    }
+
+    pub fn reviewed_by(self, user: &str) -> Self {
+        self.qualifier("reviewed-by", user)
+    }
+
+    #[allow(dead_code)]
+    pub fn review(self, state: ReviewState) -> Self {
+        self.qualifier(
+            "review",
+            match state {
+                ReviewState::None => "none",
+                ReviewState::Required => "required",
+                ReviewState::Approved => "approved",
+                ReviewState::ChangesRequested => "changes_requested",
+            },
+        )
+    }
+
+    pub fn base(self, branch: &str) -> Self {
+        self.qualifier("base", branch)
+    }
+
+    #[allow(dead_code)]
+    pub fn head(self, branch: &str) -> Self {
+        self.qualifier("head", branch)
+    }
+
+    /// Set the created range, replacing the previous one
+    pub fn created(mut self, range: DateRange) -> Self {
+        let created = Term::Dates {
+            name: "created",
+            range,
+        };
+        match self.terms.iter_mut().find(|term| {
+            matches!(
+                term,
+                Term::Dates {
+                    name: "created",
+                    ..
+                }
+            )
+        }) {
+            Some(term) => *term = created,
+            None => self.terms.push(created),
+        }
+        self
+    }
+
+    pub fn created_range(&self) -> Option<DateRange> {
+        self.terms.iter().find_map(|term| match term {
+            Term::Dates {
+                name: "created",
+                range,
+            } => Some(*range),
+            _ => None,
+        })
+    }
+
+    pub fn updated(mut self, range: DateRange) -> Self {
+        self.terms.push(Term::Dates {
+            name: "updated",
+            range,
+        });
+        self
+    }
+
+    /// Text added to the query as it is, without escaping
+    pub fn raw(mut self, text: &str) -> Self {
+        if !text.trim().is_empty() {
+            self.terms.push(Term::Raw(text.trim().to_string()));
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for DateRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DateRange::Since(time) => {
+                write!(f, ">={}", time.to_rfc3339_opts(SecondsFormat::Secs, true))
+            }
+            DateRange::Before(time) => {
+                write!(f, "<{}", time.to_rfc3339_opts(SecondsFormat::Secs, true))
+            }
+            DateRange::Between(from, to) => write!(
+                f,
+                "{}..{}",
+                from.to_rfc3339_opts(SecondsFormat::Secs, true),
+                to.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.terms
+                .iter()
+                .map(|term| match term {
+                    Term::Qualifier {
+                        negated,
+                        name,
+                        value,
+                    } => format!(
+                        "{}{name}:{}",
+                        if *negated { "-" } else { "" },
+                        escape(value)
+                    ),
+                    Term::Dates { name, range } => format!("{name}:{range}"),
+                    Term::Raw(text) => text.clone(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        )
+    }
+}
+
+/// Quote the value when it contains characters with a special meaning in a search query
+fn escape(value: &str) -> String {
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\\' | '(' | ')' | ':'))
+    {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_open_pull_requests() {
+        assert_eq!(
+            SearchQuery::open_pull_requests().to_string(),
+            "is:pr is:open"
+        );
+    }
+
+    #[test]
+    fn test_qualifiers() {
+        let query = SearchQuery::open_pull_requests()
+            .draft(false)
+            .repo("frisoft/ateam")
+            .org("frisoft")
+            .author("alice")
+            .not_author("bob")
+            .review_requested("carol")
+            .review(ReviewState::Approved)
+            .base("main");
+        assert_eq!(
+            query.to_string(),
+            "is:pr is:open draft:false repo:frisoft/ateam org:frisoft author:alice -author:bob review-requested:carol review:approved base:main"
+        );
+    }
+
+    #[test]
+    fn test_labels_are_escaped() {
+        let query = SearchQuery::default()
+            .label("bug")
+            .label("needs review")
+            .not_label("say \"hi\"");
+        assert_eq!(
+            query.to_string(),
+            "label:bug label:\"needs review\" -label:\"say \\\"hi\\\"\""
+        );
+    }
+
+    #[test]
+    fn test_label_with_colon() {
+        let query = SearchQuery::default()
+            .label("type:bug")
+            .not_label("area:ci");
+        assert_eq!(query.to_string(), "label:\"type:bug\" -label:\"area:ci\"");
+    }
+
+    #[test]
+    fn test_branches_and_users_are_quoted() {
+        let query = SearchQuery::default()
+            .user("my org")
+            .head("feature:login")
+            .review(ReviewState::ChangesRequested);
+        assert_eq!(
+            query.to_string(),
+            "user:\"my org\" head:\"feature:login\" review:changes_requested"
+        );
+    }
+
+    #[test]
+    fn test_review_states() {
+        let states = [
+            ReviewState::None,
+            ReviewState::Required,
+            ReviewState::Approved,
+            ReviewState::ChangesRequested,
+        ];
+        let query = states
+            .into_iter()
+            .fold(SearchQuery::default(), SearchQuery::review);
+        assert_eq!(
+            query.to_string(),
+            "review:none review:required review:approved review:changes_requested"
+        );
+    }
+
+    #[test]
+    fn test_date_ranges() {
+        let query = SearchQuery::default()
+            .created(DateRange::Before(time("2024-03-01T00:00:00Z")))
+            .updated(DateRange::Since(time("2024-02-01T06:30:00Z")));
+        assert_eq!(
+            query.to_string(),
+            "created:<2024-03-01T00:00:00Z updated:>=2024-02-01T06:30:00Z"
+        );
+        assert_eq!(
+            query.created_range(),
+            Some(DateRange::Before(time("2024-03-01T00:00:00Z")))
+        );
+
+        let query = query.created(DateRange::Between(
+            time("2024-01-01T00:00:00Z"),
+            time("2024-01-31T23:59:59Z"),
+        ));
+        assert_eq!(
+            query.to_string(),
+            "created:2024-01-01T00:00:00Z..2024-01-31T23:59:59Z updated:>=2024-02-01T06:30:00Z"
+        );
+    }
+
+    #[test]
+    fn test_raw_text() {
+        let query = SearchQuery::open_pull_requests()
+            .raw(" urgent fix ")
+            .raw("");
+        assert_eq!(query.to_string(), "is:pr is:open urgent fix");
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, anyhow};
+use reqwest::header::LINK;
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+// The GraphQL API exposes only the classic branch protection rules. The rules of the rulesets
+// active on a branch (required status checks and approvals) are read from the REST API.
+
+/// The rules of the rulesets active on a branch
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BranchRules {
+    pub required_checks: Vec<String>,
+    pub required_approvals: Option<u8>,
+}
+
+/// The rules of the base branches, fetched once per repository and branch
+#[derive(Debug, Default)]
+pub struct Rules {
+    branches: Mutex<HashMap<String, Arc<OnceCell<BranchRules>>>>,
+}
+
+impl Rules {
+    pub async fn branch(
+        &self,
+        github_api_token: &str,
+        owner: &str,
+        name: &str,
+        branch: &str,
+    ) -> Result<BranchRules> {
+        let cell = self
+            .branches
+            .lock()
+            .expect("not poisoned")
+            .entry(format!("{owner}/{name}:{branch}"))
+            .or_default()
+            .clone();
+        // The pull requests of the same branch wait for the first request
+        cell.get_or_try_init(|| github_rules(github_api_token, owner, name, branch))
+            .await
+            .cloned()
+    }
+}
+
+/// A rule of a ruleset. The parameters depend on the type of the rule.
+#[derive(Deserialize, Debug)]
+struct Rule {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    parameters: Option<Parameters>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Parameters {
+    #[serde(default)]
+    required_status_checks: Vec<StatusCheck>,
+    required_approving_review_count: Option<u8>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusCheck {
+    context: String,
+}
+
+async fn github_rules(
+    github_api_token: &str,
+    owner: &str,
+    name: &str,
+    branch: &str,
+) -> Result<BranchRules> {
+    let mut url = Url::parse("https://api.github.com/repos")?;
+    url.path_segments_mut()
+        .map_err(|()| anyhow!("Invalid rules URL"))?
+        .extend([owner, name, "rules", "branches", branch]);
+    url.query_pairs_mut().append_pair("per_page", "100");
+
+    let client = reqwest::Client::builder()
+        .user_agent(super::AGENT)
+        .build()?;
+    let mut rules = vec![];
+    let mut next = Some(url);
+    while let Some(url) = next {
+        let res = client
+            .get(url)
+            .header("Accept", "application/vnd.github+json")
+            .bearer_auth(github_api_token)
+            .send()
+            .await?;
+        // The rules of a repository the token can't read are not visible
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(BranchRules::default());
+        }
+        if !res.status().is_success() {
+            return Err(anyhow!(
+                "Error {} fetching the rules of {owner}/{name} {branch}",
+                res.status()
+            ));
+        }
+        next = res
+            .headers()
+            .get(LINK)
+            .and_then(|link| link.to_str().ok())
+            .and_then(next_page);
+        rules.append(&mut res.json::<Vec<Rule>>().await?);
+    }
+    Ok(parse(&rules))
+}
+
+/// The URL of the next page in a Link header: `<https://...&page=2>; rel="next", <https://...>; rel="last"`
+fn next_page(link: &str) -> Option<Url> {
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        if !params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"")
+        {
+            return None;
+        }
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        Url::parse(url).ok()
+    })
+}
+
+fn parse(rules: &[Rule]) -> BranchRules {
+    let mut branch_rules = BranchRules::default();
+    for rule in rules {
+        let Some(parameters) = &rule.parameters else {
+            continue;
+        };
+        if rule.kind == "required_status_checks" {
+            branch_rules.required_checks.extend(
+                parameters
+                    .required_status_checks
+                    .iter()
+                    .map(|check| check.context.clone()),
+            );
+        }
+        // All the rulesets apply, so the strictest one wins
+        if rule.kind == "pull_request" {
+            branch_rules.required_approvals = branch_rules
+                .required_approvals
+                .max(parameters.required_approving_review_count);
+        }
+    }
+    branch_rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let rules: Vec<Rule> = serde_json::from_str(
+            r#"[
+                {"type": "deletion", "ruleset_id": 1},
+                {"type": "required_status_checks", "ruleset_id": 2, "parameters": {
+                    "strict_required_status_checks_policy": false,
+                    "required_status_checks": [{"context": "build", "integration_id": 15368}, {"context": "lint"}]
+                }},
+                {"type": "required_linear_history", "parameters": {}},
+                {"type": "creation", "parameters": null},
+                {"type": "pull_request", "parameters": {"required_approving_review_count": 2, "require_code_owner_review": true}},
+                {"type": "pull_request", "parameters": {"required_approving_review_count": 1}},
+                {"type": "required_status_checks", "ruleset_id": 3, "parameters": {
+                    "required_status_checks": [{"context": "e2e"}]
+                }}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse(&rules),
+            BranchRules {
+                required_checks: vec!["build".to_string(), "lint".to_string(), "e2e".to_string()],
+                required_approvals: Some(2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_next_page() {
+        let link = r#"<https://api.github.com/repositories/1/rules/branches/main?per_page=100&page=2>; rel="next", <https://api.github.com/repositories/1/rules/branches/main?per_page=100&page=3>; rel="last""#;
+        assert_eq!(
+            next_page(link).unwrap().as_str(),
+            "https://api.github.com/repositories/1/rules/branches/main?per_page=100&page=2"
+        );
+        let last = r#"<https://api.github.com/repositories/1/rules/branches/main?per_page=100&page=1>; rel="prev", <https://api.github.com/repositories/1/rules/branches/main?per_page=100&page=1>; rel="first""#;
+        assert_eq!(next_page(last), None);
+        assert_eq!(next_page(""), None);
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(parse(&[]), BranchRules::default());
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt::Write;
+
+use anyhow::{Result, anyhow};
+use graphql_client::{GraphQLQuery, Response};
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/teams.graphql",
+    response_derives = "Debug"
+)]
+pub struct Teams;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/teams.graphql",
+    response_derives = "Debug"
+)]
+pub struct OrgTeams;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/client/schema.graphql",
+    query_path = "src/client/teams.graphql",
+    response_derives = "Debug"
+)]
+pub struct TeamMembers;
+
+/// A team of an organization, identified by the organization login and the team slug
+#[derive(Debug, Clone, PartialEq)]
+pub struct Team {
+    pub org: String,
+    pub slug: String,
+}
+
+/// The teams the user belongs to. They are fetched once per run to resolve the team review requests.
+pub async fn teams(github_api_token: &str, login: &str) -> Result<Vec<Team>> {
+    let mut teams = vec![];
+    let mut cursor = None;
+    loop {
+        let response_data = github_teams(github_api_token, login, cursor).await?;
+        let Some(organizations) = response_data.user.map(|user| user.organizations) else {
+            break;
+        };
+        for org in organizations.nodes.into_iter().flatten().flatten() {
+            teams.extend(
+                org.teams
+                    .nodes
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|team| Team {
+                        org: org.login.clone(),
+                        slug: team.slug,
+                    }),
+            );
+            if org.teams.page_info.has_next_page {
+                teams.extend(
+                    org_teams(
+                        github_api_token,
+                        login,
+                        &org.login,
+                        org.teams.page_info.end_cursor,
+                    )
+                    .await?,
+                );
+            }
+        }
+        if !organizations.page_info.has_next_page {
+            break;
+        }
+        cursor = organizations.page_info.end_cursor;
+    }
+    Ok(teams)
+}
+
+async fn github_teams(
+    github_api_token: &str,
+    login: &str,
+    after: Option<String>,
+) -> Result<teams::ResponseData> {
+    let q = Teams::build_query(teams::Variables {
+        login: login.to_string(),
+        after,
+    });
+
+    let res = super::call(github_api_token, &q).await?;
+
+    let response_body: Response<teams::ResponseData> = res.json().await?;
+
+    if let Some(errors) = response_body.errors {
+        let mut error_str = String::new();
+        for error in &errors {
+            write!(error_str, "{error:?}").unwrap();
+        }
+        Err(anyhow!("Errors fetching the teams of {login} {error_str}"))
+    } else {
+        match response_body.data {
+            Some(data) => Ok(data),
+            None => Err(anyhow!(
+                "Missing response data fetching the teams of {login}"
+            )),
+        }
+    }
+}
+
+/// The remaining teams of the user in an organization, after the first page
+async fn org_teams(
+    github_api_token: &str,
+    login: &str,
+    org: &str,
+    mut cursor: Option<String>,
+) -> Result<Vec<Team>> {
+    let mut teams = vec![];
+    loop {
+        let response_data = github_org_teams(github_api_token, login, org, cursor).await?;
+        let Some(org_teams) = response_data.organization.map(|org| org.teams) else {
+            return Err(anyhow!("Organization {org} not found"));
+        };
+        teams.extend(
+            org_teams
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|team| Team {
+                    org: org.to_string(),
+                    slug: team.slug,
+                }),
+        );
+        if !org_teams.page_info.has_next_page {
+            break;
+        }
+        cursor = org_teams.page_info.end_cursor;
+    }
+    Ok(teams)
+}
+
+async fn github_org_teams(
+    github_api_token: &str,
+    login: &str,
+    org: &str,
+    after: Option<String>,
+) -> Result<org_teams::ResponseData> {
+    let q = OrgTeams::build_query(org_teams::Variables {
+        org: org.to_string(),
+        login: login.to_string(),
+        after,
+    });
+
+    let res = super::call(github_api_token, &q).await?;
+
+    let response_body: Response<org_teams::ResponseData> = res.json().await?;
+
+    if let Some(errors) = response_body.errors {
+        let mut error_str = String::new();
+        for error in &errors {
+            write!(error_str, "{error:?}").unwrap();
+        }
+        Err(anyhow!(
+            "Errors fetching the teams of {login} in {org} {error_str}"
+        ))
+    } else {
+        match response_body.data {
+            Some(data) => Ok(data),
+            None => Err(anyhow!(
+                "Missing response data fetching the teams of {login} in {org}"
+            )),
+        }
+    }
+}
+
+/// The logins of the members of a team
+pub async fn members(github_api_token: &str, team: &Team) -> Result<Vec<String>> {
+    let mut logins = vec![];
+    let mut cursor = None;
+    loop {
+        let response_data = github_team_members(github_api_token, team, cursor).await?;
+        let Some(members) = response_data
+            .organization
+            .and_then(|org| org.team)
+            .map(|team| team.members)
+        else {
+            return Err(anyhow!("Team {}/{} not found", team.org, team.slug));
+        };
+        logins.extend(
+            members
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|member| member.login),
+        );
+        if !members.page_info.has_next_page {
+            break;
+        }
+        cursor = members.page_info.end_cursor;
+    }
+    Ok(logins)
+}
+
+async fn github_team_members(
+    github_api_token: &str,
+    team: &Team,
+    after: Option<String>,
+) -> Result<team_members::ResponseData> {
+    let q = TeamMembers::build_query(team_members::Variables {
+        org: team.org.clone(),
+        slug: team.slug.clone(),
+        after,
+    });
+
+    let res = super::call(github_api_token, &q).await?;
+
+    let response_body: Response<team_members::ResponseData> = res.json().await?;
+
+    if let Some(errors) = response_body.errors {
+        let mut error_str = String::new();
+        for error in &errors {
+            write!(error_str, "{error:?}").unwrap();
+        }
+        Err(anyhow!(
+            "Errors fetching the members of {}/{} {error_str}",
+            team.org,
+            team.slug
+        ))
+    } else {
+        match response_body.data {
+            Some(data) => Ok(data),
+            None => Err(anyhow!(
+                "Missing response data fetching the members of {}/{}",
+                team.org,
+                team.slug
+            )),
+        }
+    }
+}
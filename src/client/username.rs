@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use graphql_client::{GraphQLQuery, Response};
 
 #[derive(GraphQLQuery)]
@@ -9,13 +9,12 @@ use graphql_client::{GraphQLQuery, Response};
 )]
 pub struct Username;
 
-pub async fn username(github_api_token: &str) -> String {
-    let response_data: username::ResponseData = match github_username(github_api_token).await {
-        Ok(data) => data,
-        Err(e) => panic!("Can't get the username: {e:?}"),
-    };
+pub async fn username(github_api_token: &str) -> Result<String> {
+    let response_data = github_username(github_api_token)
+        .await
+        .context("Can't get the username")?;
 
-    response_data.viewer.login
+    Ok(response_data.viewer.login)
 }
 
 async fn github_username(github_api_token: &str) -> Result<username::ResponseData> {
@@ -32,5 +31,5 @@ async fn github_username(github_api_token: &str) -> Result<username::ResponseDat
         }
     }
     // println!("{:?}", response_body.data);
-    Ok(response_body.data.expect("missing response data"))
+    response_body.data.context("missing response data")
 }
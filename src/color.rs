@@ -0,0 +1,212 @@
+use super::cli::When;
+use super::types::{Label, Labels, TestsState};
+use std::io::IsTerminal;
+
+const RESET: &str = "\x1b[0m";
+
+const GREEN: (u8, u8, u8) = (46, 160, 67);
+const YELLOW: (u8, u8, u8) = (212, 167, 44);
+const ORANGE: (u8, u8, u8) = (219, 109, 40);
+const RED: (u8, u8, u8) = (218, 54, 51);
+const PURPLE: (u8, u8, u8) = (163, 113, 247);
+const GREY: (u8, u8, u8) = (125, 133, 144);
+const DARK_TEXT: (u8, u8, u8) = (31, 35, 40);
+const LIGHT_TEXT: (u8, u8, u8) = (255, 255, 255);
+// The scores are tinted from hot, the first pull request, to cold, the last one
+const HOT: (u8, u8, u8) = (255, 95, 87);
+const COLD: (u8, u8, u8) = (88, 166, 255);
+
+/// ANSI colors for the terminal. There are no colors when `Colors::detect` returns None.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Colors {
+    /// 24-bit colors, otherwise the nearest colors of the 256-color palette
+    truecolor: bool,
+}
+
+impl Colors {
+    /// Colors with --color always, or with --color auto if the output is a terminal and NO_COLOR is not set
+    pub fn detect(choice: When) -> Option<Colors> {
+        let env = |name| std::env::var(name).ok();
+        enabled(
+            choice,
+            env("NO_COLOR").as_deref(),
+            env("TERM").as_deref(),
+            std::io::stdout().is_terminal(),
+        )
+        .then(|| Colors {
+            truecolor: env("COLORTERM")
+                .is_some_and(|value| value == "truecolor" || value == "24bit"),
+        })
+    }
+
+    fn fg(self, text: &str, color: (u8, u8, u8)) -> String {
+        format!("{}{text}{RESET}", self.code(38, color))
+    }
+
+    fn code(self, layer: u8, (r, g, b): (u8, u8, u8)) -> String {
+        if self.truecolor {
+            format!("\x1b[{layer};2;{r};{g};{b}m")
+        } else {
+            format!("\x1b[{layer};5;{}m", ansi256((r, g, b)))
+        }
+    }
+
+    /// The label with its GitHub color as background
+    pub fn label(self, label: &Label) -> String {
+        let text = if label.is_light() {
+            DARK_TEXT
+        } else {
+            LIGHT_TEXT
+        };
+        format!(
+            "{}{} {} {RESET}",
+            self.code(48, label.rgb().unwrap_or((237, 237, 237))),
+            self.code(38, text),
+            label.name
+        )
+    }
+
+    pub fn labels(self, labels: &Labels) -> String {
+        labels
+            .0
+            .iter()
+            .map(|label| self.label(label))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    pub fn ci(self, tests_result: &TestsState, text: &str) -> String {
+        let color = match tests_result {
+            TestsState::Success => GREEN,
+            TestsState::Pending => YELLOW,
+            TestsState::Failure => RED,
+            TestsState::Missing => PURPLE,
+            TestsState::None => GREY,
+        };
+        self.fg(text, color)
+    }
+
+    /// Fresh pull requests in green, getting warmer with the age of the last commit
+    pub fn age(self, minutes: Option<i64>, text: &str) -> String {
+        let color = match minutes {
+            None => GREY,
+            Some(minutes) if minutes < 60 * 24 => GREEN,
+            Some(minutes) if minutes < 60 * 24 * 3 => YELLOW,
+            Some(minutes) if minutes < 60 * 24 * 7 => ORANGE,
+            Some(_) => RED,
+        };
+        self.fg(text, color)
+    }
+
+    /// The score tinted by the rank of the pull request among `count`, from 0
+    pub fn score(self, rank: usize, count: usize, text: &str) -> String {
+        #[allow(clippy::cast_precision_loss)]
+        let t = if count > 1 {
+            rank as f64 / (count - 1) as f64
+        } else {
+            0.0
+        };
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let mix = |hot: u8, cold: u8| {
+            (f64::from(hot) + (f64::from(cold) - f64::from(hot)) * t).round() as u8
+        };
+        self.fg(
+            text,
+            (mix(HOT.0, COLD.0), mix(HOT.1, COLD.1), mix(HOT.2, COLD.2)),
+        )
+    }
+}
+
+fn enabled(choice: When, no_color: Option<&str>, term: Option<&str>, is_terminal: bool) -> bool {
+    match choice {
+        When::Always => true,
+        When::Never => false,
+        When::Auto => no_color.is_none_or(str::is_empty) && term != Some("dumb") && is_terminal,
+    }
+}
+
+/// The nearest color of the 6x6x6 cube of the 256-color palette
+fn ansi256((r, g, b): (u8, u8, u8)) -> u8 {
+    let level = |c: u8| (u16::from(c) * 5 + 127) / 255;
+    #[allow(clippy::cast_possible_truncation)]
+    let index = 16 + 36 * level(r) + 6 * level(g) + level(b);
+    index as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRUECOLOR: Colors = Colors { truecolor: true };
+
+    #[test]
+    fn test_enabled() {
+        assert!(enabled(When::Always, Some("1"), None, false));
+        assert!(!enabled(When::Never, None, None, true));
+        assert!(enabled(When::Auto, None, Some("xterm"), true));
+        assert!(enabled(When::Auto, Some(""), None, true));
+        assert!(!enabled(When::Auto, Some("1"), None, true));
+        assert!(!enabled(When::Auto, None, Some("dumb"), true));
+        assert!(!enabled(When::Auto, None, None, false));
+    }
+
+    #[test]
+    fn test_label() {
+        let label = Label {
+            name: "bug".to_string(),
+            color: "d73a4a".to_string(),
+        };
+        assert_eq!(
+            TRUECOLOR.label(&label),
+            "\x1b[48;2;215;58;74m\x1b[38;2;255;255;255m bug \x1b[0m"
+        );
+        assert_eq!(
+            Colors { truecolor: false }.label(&label),
+            "\x1b[48;5;167m\x1b[38;5;231m bug \x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_ci_and_age() {
+        assert_eq!(
+            TRUECOLOR.ci(&TestsState::Failure, "Fail"),
+            "\x1b[38;2;218;54;51mFail\x1b[0m"
+        );
+        assert!(
+            TRUECOLOR
+                .age(Some(30), "30m")
+                .starts_with("\x1b[38;2;46;160;67m")
+        );
+        assert!(
+            TRUECOLOR
+                .age(Some(60 * 24 * 10), "10d")
+                .starts_with("\x1b[38;2;218;54;51m")
+        );
+    }
+
+    #[test]
+    fn test_score() {
+        assert!(
+            TRUECOLOR
+                .score(0, 3, "1")
+                .starts_with("\x1b[38;2;255;95;87m")
+        );
+        assert!(
+            TRUECOLOR
+                .score(2, 3, "1")
+                .starts_with("\x1b[38;2;88;166;255m")
+        );
+        assert!(
+            TRUECOLOR
+                .score(0, 1, "1")
+                .starts_with("\x1b[38;2;255;95;87m")
+        );
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(ansi256((0, 0, 0)), 16);
+        assert_eq!(ansi256((255, 255, 255)), 231);
+        assert_eq!(ansi256((255, 0, 0)), 196);
+    }
+}
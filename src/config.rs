@@ -1,13 +1,30 @@
+use ateam::cli::{PrArgs, RepoApprovals};
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub github_api_token: String,
+    /// The required approvals of the repositories: OWNER/REPO=NUM,OWNER/REPO=NUM
+    #[serde(default)]
+    pub ateam_repo_approvals: Vec<RepoApprovals>,
 }
 
-pub fn get_config() -> Result<Config, envy::Error> {
+impl Config {
+    /// Complete the options of the command line with the configuration. The options take precedence.
+    pub fn apply(&self, options: &mut PrArgs) {
+        options
+            .repo_approvals
+            .extend(self.ateam_repo_approvals.iter().cloned());
+    }
+}
+
+/// Load the .env file, so it can set the command line defaults too.
+pub fn load_env() {
     dotenvy::dotenv().ok();
     let _ = env_logger::try_init();
+}
+
+pub fn get_config() -> Result<Config, envy::Error> {
     envy::from_env()
 }
 
@@ -24,6 +41,39 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_config_repo_approvals() {
+        temp_env::with_vars(
+            [
+                ("GITHUB_API_TOKEN", Some("test_token_abc123")),
+                ("ATEAM_REPO_APPROVALS", Some("acme/api=1,acme/web=3")),
+            ],
+            || {
+                let config: Config = envy::from_env().expect("Failed to deserialize");
+                assert_eq!(
+                    config.ateam_repo_approvals,
+                    vec![
+                        RepoApprovals {
+                            repo: "acme/api".to_string(),
+                            approvals: 1
+                        },
+                        RepoApprovals {
+                            repo: "acme/web".to_string(),
+                            approvals: 3
+                        }
+                    ]
+                );
+            },
+        );
+        temp_env::with_vars(
+            [
+                ("GITHUB_API_TOKEN", Some("test_token_abc123")),
+                ("ATEAM_REPO_APPROVALS", Some("acme/api")),
+            ],
+            || assert!(envy::from_env::<Config>().is_err()),
+        );
+    }
+
     #[test]
     fn test_config_missing_token() {
         // Unset both GITHUB_API_TOKEN and any dotenv override (GITHUB_TOKEN)
@@ -0,0 +1,231 @@
+use super::types::{Review, ScoredPr};
+use anyhow::Result;
+use csv::WriterBuilder;
+
+/// The columns of the CSV and TSV outputs. Append new columns at the end: scripts rely on the positions.
+const PR_COLUMNS: [&str; 40] = [
+    "url",
+    "title",
+    "repo",
+    "author",
+    "bot",
+    "last_commit_pushed_date",
+    "last_commit_age_min",
+    "tests_result",
+    "all_tests_result",
+    "failed_checks",
+    "open_conversations",
+    "num_approvals",
+    "num_reviewers",
+    "required_approvals",
+    "additions",
+    "deletions",
+    "changed_files",
+    "based_on_main_branch",
+    "merge_state",
+    "files",
+    "blame",
+    "labels",
+    "requested",
+    "codeowner",
+    "team",
+    "reviewed_by_me",
+    "score_age",
+    "score_tests_result",
+    "score_open_conversations",
+    "score_num_approvals",
+    "score_num_reviewers",
+    "score_additions",
+    "score_deletions",
+    "score_based_on_main_branch",
+    "score_merge_state",
+    "score_blame",
+    "score_requested",
+    "score_codeowner",
+    "score",
+    "created_at",
+];
+
+const REVIEW_COLUMNS: [&str; 3] = ["url", "state", "pr_title"];
+
+/// Delimiter separated values: b',' for CSV and b'\t' for TSV.
+/// Fields with the delimiter, quotes or new lines are quoted. Lists are separated by semicolons.
+pub fn from(sprs: &[ScoredPr], limit: usize, delimiter: u8, header: bool) -> Result<String> {
+    write(
+        delimiter,
+        header.then_some(&PR_COLUMNS[..]),
+        sprs.iter().take(limit).map(pr_record),
+    )
+}
+
+pub fn from_reviews(reviews: &[Review], delimiter: u8, header: bool) -> Result<String> {
+    write(
+        delimiter,
+        header.then_some(&REVIEW_COLUMNS[..]),
+        reviews.iter().map(|review| {
+            vec![
+                review.url.clone(),
+                review.state.to_string(),
+                review.pr_title.clone(),
+            ]
+        }),
+    )
+}
+
+fn write<I>(delimiter: u8, header: Option<&[&str]>, records: I) -> Result<String>
+where
+    I: Iterator<Item = Vec<String>>,
+{
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+    if let Some(header) = header {
+        writer.write_record(header)?;
+    }
+    for record in records {
+        writer.write_record(record)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn pr_record(spr: &ScoredPr) -> Vec<String> {
+    let pr = &spr.pr;
+    let score = &spr.score;
+    vec![
+        pr.url.clone(),
+        pr.title.clone(),
+        pr.repo.clone(),
+        pr.author.clone(),
+        pr.bot.to_string(),
+        pr.last_commit_pushed_date
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default(),
+        pr.last_commit_age_min
+            .map(|age| age.to_string())
+            .unwrap_or_default(),
+        format!("{:?}", pr.tests_result),
+        format!("{:?}", pr.all_tests_result),
+        pr.failed_checks.join(";"),
+        pr.open_conversations.to_string(),
+        pr.num_approvals.to_string(),
+        pr.num_reviewers.to_string(),
+        pr.required_approvals.to_string(),
+        pr.additions.to_string(),
+        pr.deletions.to_string(),
+        pr.changed_files.to_string(),
+        pr.based_on_main_branch.to_string(),
+        format!("{:?}", pr.merge_state),
+        pr.files.0.join(";"),
+        pr.blame.to_string(),
+        pr.labels
+            .0
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect::<Vec<_>>()
+            .join(";"),
+        pr.requested.to_string(),
+        pr.codeowner.to_string(),
+        pr.team.clone().unwrap_or_default(),
+        pr.reviewed_by_me.to_string(),
+        score.age.to_string(),
+        score.tests_result.to_string(),
+        score.open_conversations.to_string(),
+        score.num_approvals.to_string(),
+        score.num_reviewers.to_string(),
+        score.additions.to_string(),
+        score.deletions.to_string(),
+        score.based_on_main_branch.to_string(),
+        score.merge_state.to_string(),
+        score.blame.to_string(),
+        score.requested.to_string(),
+        score.codeowner.to_string(),
+        score.total().to_string(),
+        pr.created_at
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::*;
+    use super::*;
+
+    fn make_scored_pr(title: &str, labels: &[&str]) -> ScoredPr {
+        test_scored_pr(Pr {
+            title: title.to_string(),
+            created_at: Some("2024-03-01T09:30:00Z".parse().unwrap()),
+            last_commit_age_min: Some(90),
+            tests_result: TestsState::Failure,
+            all_tests_result: TestsState::Failure,
+            failed_checks: vec!["lint".to_string(), "unit".to_string()],
+            open_conversations: 1,
+            num_reviewers: 1,
+            additions: 10,
+            deletions: 2,
+            changed_files: 3,
+            based_on_main_branch: true,
+            merge_state: MergeState::Behind,
+            labels: Labels(
+                labels
+                    .iter()
+                    .map(|name| Label {
+                        name: (*name).to_string(),
+                        color: "ededed".to_string(),
+                    })
+                    .collect(),
+            ),
+            requested: true,
+            ..test_pr()
+        })
+    }
+
+    #[test]
+    fn test_csv() {
+        let prs = vec![make_scored_pr(
+            "Fix \"quotes\", commas",
+            &["bug", "needs review"],
+        )];
+        let result = from(&prs, 10, b',', true).unwrap();
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("url,title,repo,author,bot,"));
+        assert!(lines[0].ends_with(",score_codeowner,score,created_at"));
+        assert!(lines[1].starts_with(
+            "https://github.com/frisoft/ateam/pull/1,\"Fix \"\"quotes\"\", commas\",frisoft/ateam,alice,false,,90,Failure,Failure,lint;unit,1,0,1,1,10,2,3,true,Behind,,false,bug;needs review,true,false,,false,3,"
+        ));
+        assert!(lines[1].ends_with(",2024-03-01T09:30:00+00:00"));
+        assert_eq!(lines[1].split(',').count(), PR_COLUMNS.len() + 1);
+    }
+
+    #[test]
+    fn test_tsv_without_header() {
+        let prs = vec![
+            make_scored_pr("Tabs\tand\nnew lines", &[]),
+            make_scored_pr("Second", &[]),
+        ];
+        let result = from(&prs, 1, b'\t', false).unwrap();
+        assert!(
+            result
+                .starts_with("https://github.com/frisoft/ateam/pull/1\t\"Tabs\tand\nnew lines\"\t")
+        );
+        assert!(!result.contains("Second"));
+    }
+
+    #[test]
+    fn test_reviews() {
+        let reviews = vec![Review {
+            state: ReviewState::WithAddressedConversations,
+            url: "https://example.com/1".to_string(),
+            pr_title: "Fix bug".to_string(),
+        }];
+        assert_eq!(
+            from_reviews(&reviews, b',', true).unwrap(),
+            "url,state,pr_title\nhttps://example.com/1,With addressed conversations,Fix bug\n"
+        );
+        assert_eq!(
+            from_reviews(&[], b'\t', true).unwrap(),
+            "url\tstate\tpr_title\n"
+        );
+    }
+}
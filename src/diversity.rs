@@ -0,0 +1,166 @@
+use super::cli::{PrArgs, RoundRobin};
+use super::types::ScoredPr;
+use std::collections::HashMap;
+
+/// Spread the review attention over repositories and authors: apply the --max-per-repo and
+/// --max-per-author quotas to the sorted pull requests, then interleave them with --round-robin.
+pub fn spread(sprs: Vec<ScoredPr>, options: &PrArgs) -> Vec<ScoredPr> {
+    let sprs = quota(sprs, options.max_per_repo, |spr| &spr.pr.repo);
+    let sprs = quota(sprs, options.max_per_author, |spr| &spr.pr.author);
+    match options.round_robin {
+        Some(RoundRobin::Repo) => round_robin(sprs, |spr| &spr.pr.repo),
+        Some(RoundRobin::Author) => round_robin(sprs, |spr| &spr.pr.author),
+        None => sprs,
+    }
+}
+
+/// True if the options change the order or the number of the ranked pull requests
+pub fn enabled(options: &PrArgs) -> bool {
+    options.max_per_repo.is_some()
+        || options.max_per_author.is_some()
+        || options.round_robin.is_some()
+}
+
+/// Keep at most `max` pull requests with the same key, the first ones
+fn quota<F>(sprs: Vec<ScoredPr>, max: Option<usize>, key: F) -> Vec<ScoredPr>
+where
+    F: Fn(&ScoredPr) -> &String,
+{
+    let Some(max) = max else {
+        return sprs;
+    };
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    sprs.into_iter()
+        .filter(|spr| {
+            let count = counts.entry(key(spr).to_lowercase()).or_insert(0);
+            *count += 1;
+            *count <= max
+        })
+        .collect()
+}
+
+/// Take the first pull request of every key in turn. The keys are in the order of their first pull request.
+fn round_robin<F>(sprs: Vec<ScoredPr>, key: F) -> Vec<ScoredPr>
+where
+    F: Fn(&ScoredPr) -> &String,
+{
+    let mut groups: Vec<Vec<ScoredPr>> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for spr in sprs {
+        let group = *index.entry(key(&spr).to_lowercase()).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push(spr);
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().map(Vec::into_iter).collect();
+    let mut result = vec![];
+    loop {
+        let round: Vec<ScoredPr> = groups.iter_mut().filter_map(Iterator::next).collect();
+        if round.is_empty() {
+            break;
+        }
+        result.extend(round);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::*;
+    use super::*;
+
+    fn make_scored_pr(repo: &str, author: &str, n: u8) -> ScoredPr {
+        test_scored_pr(Pr {
+            title: format!("{repo} {author} {n}"),
+            url: format!("https://github.com/{repo}/pull/{n}"),
+            repo: repo.to_string(),
+            author: author.to_string(),
+            ..test_pr()
+        })
+    }
+
+    fn titles(sprs: &[ScoredPr]) -> Vec<&str> {
+        sprs.iter().map(|spr| spr.pr.title.as_str()).collect()
+    }
+
+    fn sorted() -> Vec<ScoredPr> {
+        vec![
+            make_scored_pr("acme/api", "alice", 1),
+            make_scored_pr("acme/api", "bob", 2),
+            make_scored_pr("acme/api", "alice", 3),
+            make_scored_pr("acme/web", "alice", 4),
+            make_scored_pr("acme/cli", "carol", 5),
+            make_scored_pr("acme/web", "bob", 6),
+        ]
+    }
+
+    #[test]
+    fn test_quota_per_repo() {
+        let result = quota(sorted(), Some(1), |spr| &spr.pr.repo);
+        assert_eq!(
+            titles(&result),
+            vec!["acme/api alice 1", "acme/web alice 4", "acme/cli carol 5"]
+        );
+    }
+
+    #[test]
+    fn test_quota_per_author() {
+        let result = quota(sorted(), Some(2), |spr| &spr.pr.author);
+        assert_eq!(
+            titles(&result),
+            vec![
+                "acme/api alice 1",
+                "acme/api bob 2",
+                "acme/api alice 3",
+                "acme/cli carol 5",
+                "acme/web bob 6",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enabled() {
+        use clap::Parser;
+        let options = |args: &[&str]| {
+            let mut argv = vec!["ateam", "pr"];
+            argv.extend_from_slice(args);
+            match super::super::cli::Ateam::parse_from(argv).cmd {
+                super::super::cli::Command::Pr(options) => options,
+                super::super::cli::Command::Followup(_) | super::super::cli::Command::Tui(_) => {
+                    unreachable!()
+                }
+            }
+        };
+        assert!(!enabled(&options(&[])));
+        assert!(enabled(&options(&["--max-per-repo", "2"])));
+        assert!(enabled(&options(&["--round-robin", "author"])));
+    }
+
+    #[test]
+    fn test_quota_none() {
+        assert_eq!(quota(sorted(), None, |spr| &spr.pr.repo).len(), 6);
+    }
+
+    #[test]
+    fn test_round_robin_by_repo() {
+        let result = round_robin(sorted(), |spr| &spr.pr.repo);
+        assert_eq!(
+            titles(&result),
+            vec![
+                "acme/api alice 1",
+                "acme/web alice 4",
+                "acme/cli carol 5",
+                "acme/api bob 2",
+                "acme/web bob 6",
+                "acme/api alice 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_robin_empty() {
+        assert!(round_robin(vec![], |spr| &spr.pr.author).is_empty());
+    }
+}
@@ -0,0 +1,696 @@
+use super::cli::parse_duration;
+use super::filter::Stage;
+use super::types::{MergeState, Pr, TestsState};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// A boolean expression over the fields of a pull request, used by --where:
+///
+/// `ci != "fail" && additions < 400 && (requested || codeowner) && age > 1d`
+#[derive(Debug, Clone)]
+pub struct Expr {
+    source: String,
+    node: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Bool(bool),
+    Field(Field),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Field, Op, Literal),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Url,
+    Author,
+    Ci,
+    Merge,
+    Team,
+    Labels,
+    Additions,
+    Deletions,
+    Lines,
+    Files,
+    Conversations,
+    Approvals,
+    Reviewers,
+    RequiredApprovals,
+    Age,
+    Requested,
+    Codeowner,
+    Blame,
+    OnMain,
+    Bot,
+    ReviewedByMe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Bool,
+    Number,
+    Duration,
+    Text,
+    List,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+#[derive(Debug, Clone)]
+enum Literal {
+    Bool(bool),
+    Number(i64),
+    // In minutes, like the age of the pull requests
+    Duration(i64),
+    Text(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Text(String),
+    Number(i64),
+    Duration(i64),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LeftParen,
+    RightParen,
+}
+
+/// A message and the position in the expression it refers to
+type Error = (usize, String);
+
+const FIELDS: [(&str, Field, Type); 22] = [
+    ("title", Field::Title, Type::Text),
+    ("url", Field::Url, Type::Text),
+    ("author", Field::Author, Type::Text),
+    ("ci", Field::Ci, Type::Text),
+    ("merge", Field::Merge, Type::Text),
+    ("team", Field::Team, Type::Text),
+    ("labels", Field::Labels, Type::List),
+    ("additions", Field::Additions, Type::Number),
+    ("deletions", Field::Deletions, Type::Number),
+    ("lines", Field::Lines, Type::Number),
+    ("files", Field::Files, Type::Number),
+    ("conversations", Field::Conversations, Type::Number),
+    ("approvals", Field::Approvals, Type::Number),
+    ("reviewers", Field::Reviewers, Type::Number),
+    ("required_approvals", Field::RequiredApprovals, Type::Number),
+    ("age", Field::Age, Type::Duration),
+    ("requested", Field::Requested, Type::Bool),
+    ("codeowner", Field::Codeowner, Type::Bool),
+    ("blame", Field::Blame, Type::Bool),
+    ("on_main", Field::OnMain, Type::Bool),
+    ("bot", Field::Bot, Type::Bool),
+    ("reviewed_by_me", Field::ReviewedByMe, Type::Bool),
+];
+
+const CI_VALUES: [&str; 5] = ["ok", "pending", "fail", "miss", "none"];
+
+const MERGE_VALUES: [&str; 7] = [
+    "clean", "behind", "blocked", "unstable", "hooks", "conflict", "unknown",
+];
+
+impl Expr {
+    /// Both expressions must match
+    #[cfg(test)]
+    #[must_use]
+    fn and(self, other: Expr) -> Expr {
+        Expr {
+            source: format!("({}) && ({})", self.source, other.source),
+            node: Node::And(Box::new(self.node), Box::new(other.node)),
+        }
+    }
+
+    pub(crate) fn matches(&self, pr: &Pr) -> bool {
+        eval(&self.node, pr)
+    }
+
+    /// The first stage with all the fields of the expression
+    pub(crate) fn stage(&self) -> Stage {
+        self.node.stage()
+    }
+
+    /// The conditions joined by the top-level &&, so every one of them can run at its own stage
+    pub(crate) fn conjuncts(&self) -> Vec<Expr> {
+        let Ok(tokens) = tokenize(&self.source) else {
+            return vec![self.clone()];
+        };
+        let mut depth = 0;
+        let mut ands = vec![];
+        for (position, token) in &tokens {
+            match token {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth -= 1,
+                Token::Or if depth == 0 => return vec![self.clone()],
+                Token::And if depth == 0 => ands.push(*position),
+                _ => {}
+            }
+        }
+        let starts = std::iter::once(0).chain(ands.iter().map(|position| position + 2));
+        let ends = ands
+            .iter()
+            .copied()
+            .chain(std::iter::once(self.source.len()));
+        starts
+            .zip(ends)
+            .map(|(start, end)| self.source[start..end].trim().parse())
+            .collect::<Result<Vec<Expr>, String>>()
+            .unwrap_or_else(|_| vec![self.clone()])
+    }
+}
+
+impl Node {
+    fn stage(&self) -> Stage {
+        match self {
+            Node::Bool(_) => Stage::Search,
+            Node::Field(field) | Node::Compare(field, _, _) => field.stage(),
+            Node::Not(node) => node.stage(),
+            Node::And(left, right) | Node::Or(left, right) => left.stage().max(right.stage()),
+        }
+    }
+}
+
+impl Field {
+    fn stage(self) -> Stage {
+        match self {
+            Field::Title
+            | Field::Url
+            | Field::Author
+            | Field::Bot
+            | Field::Merge
+            | Field::Additions
+            | Field::Deletions
+            | Field::Lines
+            | Field::Files
+            | Field::OnMain => Stage::Search,
+            Field::Ci | Field::Age | Field::RequiredApprovals => Stage::Commit,
+            Field::Team
+            | Field::Labels
+            | Field::Conversations
+            | Field::Approvals
+            | Field::Reviewers
+            | Field::Requested
+            | Field::Codeowner
+            | Field::ReviewedByMe => Stage::Connections,
+            Field::Blame => Stage::Blame,
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let node = tokenize(s).and_then(|tokens| {
+            let mut parser = Parser {
+                tokens,
+                next: 0,
+                end: s.len(),
+            };
+            let node = parser.or()?;
+            match parser.peek() {
+                None => Ok(node),
+                Some((position, _)) => Err((position, "expected && or ||".to_string())),
+            }
+        });
+        match node {
+            Ok(node) => Ok(Expr {
+                source: s.to_string(),
+                node,
+            }),
+            Err((position, message)) => {
+                Err(format!("{message}\n    {s}\n    {}^", " ".repeat(position)))
+            }
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, Error> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if(|(_, c)| *c == expected).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '~' => Token::Op(Op::Match),
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' if next_is('=') => Token::Op(Op::Eq),
+            '!' if next_is('=') => Token::Op(Op::Ne),
+            '!' if next_is('~') => Token::Op(Op::NotMatch),
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if next_is('=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => text.push(c),
+                            None => return Err((start, "unterminated string".to_string())),
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => return Err((start, "unterminated string".to_string())),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if c.is_ascii_digit() => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric()) {
+                    word.push(c);
+                }
+                if word.chars().all(|c| c.is_ascii_digit()) {
+                    Token::Number(
+                        word.parse()
+                            .map_err(|_| (start, "number too large".to_string()))?,
+                    )
+                } else {
+                    Token::Duration(parse_duration(&word).map_err(|e| (start, e))?.num_minutes())
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    word.push(c);
+                }
+                Token::Ident(word)
+            }
+            c => return Err((start, format!("unexpected character {c:?}"))),
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens
+            .get(self.next)
+            .map(|(position, token)| (*position, token))
+    }
+
+    fn advance(&mut self) -> Result<(usize, Token), Error> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| (self.end, "unexpected end of the expression".to_string()))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<Node, Error> {
+        let mut node = self.and()?;
+        while let Some((_, Token::Or)) = self.peek() {
+            self.next += 1;
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, Error> {
+        let mut node = self.unary()?;
+        while let Some((_, Token::And)) = self.peek() {
+            self.next += 1;
+            node = Node::And(Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, Error> {
+        match self.advance()? {
+            (_, Token::Not) => Ok(Node::Not(Box::new(self.unary()?))),
+            (_, Token::LeftParen) => {
+                let node = self.or()?;
+                match self.advance()? {
+                    (_, Token::RightParen) => Ok(node),
+                    (position, _) => Err((position, "expected )".to_string())),
+                }
+            }
+            (_, Token::Ident(name)) if name == "true" => Ok(Node::Bool(true)),
+            (_, Token::Ident(name)) if name == "false" => Ok(Node::Bool(false)),
+            (position, Token::Ident(name)) => self.comparison(position, &name),
+            (position, _) => Err((position, "expected a field, ! or (".to_string())),
+        }
+    }
+
+    fn comparison(&mut self, position: usize, name: &str) -> Result<Node, Error> {
+        let (field, kind) = FIELDS
+            .iter()
+            .find(|(field_name, _, _)| *field_name == name)
+            .map(|(_, field, kind)| (*field, *kind))
+            .ok_or_else(|| {
+                (
+                    position,
+                    format!(
+                        "unknown field {name}: use {}",
+                        FIELDS.map(|(field_name, _, _)| field_name).join(", ")
+                    ),
+                )
+            })?;
+        let Some((_, Token::Op(op))) = self.peek() else {
+            return if kind == Type::Bool {
+                Ok(Node::Field(field))
+            } else {
+                Err((position, format!("{name} must be compared with a value")))
+            };
+        };
+        let op = *op;
+        self.next += 1;
+        let (position, token) = self.advance()?;
+        literal(field, name, kind, op, token)
+            .map(|literal| Node::Compare(field, op, literal))
+            .map_err(|message| (position, message))
+    }
+}
+
+fn literal(field: Field, name: &str, kind: Type, op: Op, token: Token) -> Result<Literal, String> {
+    let ordering = matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge);
+    let matching = matches!(op, Op::Match | Op::NotMatch);
+    match (kind, token) {
+        (Type::Bool, Token::Ident(value)) if !ordering && !matching => match value.as_str() {
+            "true" => Ok(Literal::Bool(true)),
+            "false" => Ok(Literal::Bool(false)),
+            _ => Err(format!("{name} is compared with true or false")),
+        },
+        (Type::Number, Token::Number(value)) if !matching => Ok(Literal::Number(value)),
+        (Type::Duration, Token::Duration(value)) if !matching => Ok(Literal::Duration(value)),
+        (Type::Text | Type::List, Token::Text(value)) if matching => Regex::new(&value)
+            .map(Literal::Regex)
+            .map_err(|e| format!("invalid regex: {e}")),
+        (Type::Text | Type::List, Token::Text(value)) if !ordering => {
+            let values: &[&str] = match field {
+                Field::Ci => &CI_VALUES,
+                Field::Merge => &MERGE_VALUES,
+                _ => &[],
+            };
+            if values.is_empty() || values.iter().any(|v| v.eq_ignore_ascii_case(&value)) {
+                Ok(Literal::Text(value))
+            } else {
+                Err(format!(
+                    "unknown {name} value {value:?}: use {}",
+                    values.join(", ")
+                ))
+            }
+        }
+        (Type::Bool, _) => Err(format!(
+            "{name} is compared with == or != and true or false"
+        )),
+        (Type::Number, _) => Err(format!("{name} is compared with a number, like 400")),
+        (Type::Duration, _) => Err(format!("{name} is compared with a duration, like 2d or 6h")),
+        (Type::Text | Type::List, _) => Err(format!(
+            "{name} is compared with a string, using ==, != or the regex operators ~ and !~"
+        )),
+    }
+}
+
+fn eval(node: &Node, pr: &Pr) -> bool {
+    match node {
+        Node::Bool(value) => *value,
+        Node::Field(field) => matches!(bool_value(*field, pr), Some(true)),
+        Node::Not(node) => !eval(node, pr),
+        Node::And(left, right) => eval(left, pr) && eval(right, pr),
+        Node::Or(left, right) => eval(left, pr) || eval(right, pr),
+        Node::Compare(field, op, literal) => compare(*field, *op, literal, pr),
+    }
+}
+
+fn compare(field: Field, op: Op, literal: &Literal, pr: &Pr) -> bool {
+    match literal {
+        Literal::Bool(value) => bool_value(field, pr).is_some_and(|v| ordered(&v, op, value)),
+        Literal::Number(value) => number_value(field, pr).is_some_and(|v| ordered(&v, op, value)),
+        Literal::Duration(value) => pr.age_min().is_some_and(|v| ordered(&v, op, value)),
+        Literal::Text(value) => {
+            let any = text_values(field, pr)
+                .iter()
+                .any(|v| v.eq_ignore_ascii_case(value));
+            if op == Op::Ne { !any } else { any }
+        }
+        Literal::Regex(re) => {
+            let any = text_values(field, pr).iter().any(|v| re.is_match(v));
+            if op == Op::NotMatch { !any } else { any }
+        }
+    }
+}
+
+fn ordered<T: PartialOrd>(value: &T, op: Op, literal: &T) -> bool {
+    let ordering = value.partial_cmp(literal);
+    match op {
+        Op::Eq => ordering == Some(Ordering::Equal),
+        Op::Ne => ordering != Some(Ordering::Equal),
+        Op::Lt => ordering == Some(Ordering::Less),
+        Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Op::Gt => ordering == Some(Ordering::Greater),
+        Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        Op::Match | Op::NotMatch => false,
+    }
+}
+
+fn bool_value(field: Field, pr: &Pr) -> Option<bool> {
+    match field {
+        Field::Requested => Some(pr.requested),
+        Field::Codeowner => Some(pr.codeowner),
+        Field::Blame => Some(pr.blame),
+        Field::OnMain => Some(pr.based_on_main_branch),
+        Field::Bot => Some(pr.bot),
+        Field::ReviewedByMe => Some(pr.reviewed_by_me),
+        _ => None,
+    }
+}
+
+fn number_value(field: Field, pr: &Pr) -> Option<i64> {
+    match field {
+        Field::Additions => Some(pr.additions),
+        Field::Deletions => Some(pr.deletions),
+        Field::Lines => Some(pr.additions + pr.deletions),
+        Field::Files => Some(pr.changed_files),
+        Field::Conversations => Some(pr.open_conversations),
+        Field::Approvals => Some(pr.num_approvals),
+        Field::Reviewers => Some(pr.num_reviewers),
+        Field::RequiredApprovals => Some(i64::from(pr.required_approvals)),
+        _ => None,
+    }
+}
+
+fn text_values(field: Field, pr: &Pr) -> Vec<&str> {
+    match field {
+        Field::Title => vec![&pr.title],
+        Field::Url => vec![&pr.url],
+        Field::Author => vec![&pr.author],
+        Field::Ci => vec![ci_value(&pr.tests_result)],
+        Field::Merge => vec![merge_value(&pr.merge_state)],
+        Field::Team => vec![pr.team.as_deref().unwrap_or_default()],
+        Field::Labels => pr
+            .labels
+            .0
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn ci_value(tests_result: &TestsState) -> &'static str {
+    match tests_result {
+        TestsState::Success => "ok",
+        TestsState::Pending => "pending",
+        TestsState::Failure => "fail",
+        TestsState::Missing => "miss",
+        TestsState::None => "none",
+    }
+}
+
+fn merge_value(merge_state: &MergeState) -> &'static str {
+    match merge_state {
+        MergeState::Clean => "clean",
+        MergeState::Behind => "behind",
+        MergeState::Blocked => "blocked",
+        MergeState::Unstable => "unstable",
+        MergeState::HasHooks => "hooks",
+        MergeState::Conflicting => "conflict",
+        MergeState::Unknown => "unknown",
+    }
+}
+
+/// A string literal of the expression language
+#[must_use]
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::*;
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn make_pr() -> Pr {
+        Pr {
+            title: "Fix the login page".to_string(),
+            last_commit_age_min: Some(3 * 24 * 60),
+            open_conversations: 1,
+            num_approvals: 1,
+            num_reviewers: 2,
+            required_approvals: 2,
+            additions: 300,
+            deletions: 50,
+            based_on_main_branch: true,
+            merge_state: MergeState::Behind,
+            labels: Labels(vec![Label {
+                name: "bug".to_string(),
+                color: "ff0000".to_string(),
+            }]),
+            codeowner: true,
+            ..test_pr()
+        }
+    }
+
+    fn matches(source: &str) -> bool {
+        source.parse::<Expr>().unwrap().matches(&make_pr())
+    }
+
+    #[test]
+    fn test_example() {
+        assert!(matches(
+            r#"ci != "Fail" && additions < 400 && (requested || codeowner) && age > 1d"#
+        ));
+    }
+
+    #[test]
+    fn test_numbers_and_durations() {
+        assert!(matches("lines == 350 && files < 2"));
+        assert!(matches("approvals < 2 && required_approvals == 2"));
+        assert!(!matches("additions >= 301"));
+        assert!(matches("age <= 3d && age > 71h"));
+        assert!(!matches("age < 2w && age < 30m"));
+    }
+
+    #[test]
+    fn test_strings_and_lists() {
+        assert!(matches(r#"author == "Alice" && merge == "behind""#));
+        assert!(matches(r#"title ~ "^Fix" && title !~ "(?i)docs""#));
+        assert!(matches(r#"labels == "bug" && labels != "wip""#));
+        assert!(matches(r#"team == """#));
+    }
+
+    #[test]
+    fn test_booleans_and_precedence() {
+        assert!(matches("!requested && codeowner == true"));
+        assert!(matches("requested && blame || on_main"));
+        assert!(!matches("requested && (blame || on_main)"));
+        assert!(matches("!bot && !reviewed_by_me"));
+    }
+
+    #[test]
+    fn test_missing_age() {
+        let mut pr = make_pr();
+        pr.last_commit_age_min = None;
+        let expr: Expr = "age > 1d || age <= 1d".parse().unwrap();
+        assert!(!expr.matches(&pr));
+    }
+
+    #[test]
+    fn test_age_of_the_creation() {
+        let mut pr = make_pr();
+        pr.last_commit_age_min = None;
+        pr.created_at = Some(Utc::now() - Duration::days(3));
+        let expr: Expr = "age > 2d && age < 4d".parse().unwrap();
+        assert!(expr.matches(&pr));
+
+        pr.last_commit_age_min = Some(60);
+        assert!(!expr.matches(&pr));
+    }
+
+    #[test]
+    fn test_and() {
+        let expr = "requested"
+            .parse::<Expr>()
+            .unwrap()
+            .and("codeowner".parse().unwrap());
+        assert_eq!(expr.to_string(), "(requested) && (codeowner)");
+        assert!(!expr.matches(&make_pr()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "additions < && codeowner".parse::<Expr>().unwrap_err(),
+            "additions is compared with a number, like 400\n    additions < && codeowner\n                ^"
+        );
+        assert!(
+            "size > 10"
+                .parse::<Expr>()
+                .unwrap_err()
+                .starts_with("unknown field size")
+        );
+        assert!(
+            r#"ci == "failed""#
+                .parse::<Expr>()
+                .unwrap_err()
+                .starts_with("unknown ci value \"failed\"")
+        );
+        assert!(
+            "age > 10"
+                .parse::<Expr>()
+                .unwrap_err()
+                .starts_with("age is compared with a duration")
+        );
+        assert!(r#"title ~ "(""#.parse::<Expr>().unwrap_err().starts_with("invalid regex"));
+        assert!("(requested".parse::<Expr>().is_err());
+        assert!("requested codeowner".parse::<Expr>().is_err());
+        assert!(r#"title == "open"#.parse::<Expr>().is_err());
+        assert!("additions".parse::<Expr>().is_err());
+        assert!(
+            "age > 99999999999999w"
+                .parse::<Expr>()
+                .unwrap_err()
+                .starts_with("duration too large")
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        let source = format!("author == {}", quote("say \"hi\""));
+        assert_eq!(source, r#"author == "say \"hi\"""#);
+        assert!(source.parse::<Expr>().is_ok());
+    }
+}
@@ -1,111 +1,622 @@
-use super::types::ScoredPr;
+use super::cli::PrArgs;
+use super::expr::{Expr, quote};
+use super::types::Pr;
+use anyhow::{Result, anyhow};
+use chrono::Duration;
 use regex::Regex;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[allow(dead_code)]
-pub fn filter_prs(regex_text: Option<&String>, prs: Vec<ScoredPr>) -> Vec<ScoredPr> {
-    if let Some(regex_text) = regex_text {
-        let re = Regex::new(regex_text).unwrap();
-        prs.into_iter()
-            .filter(|pr| re.is_match(&pr.pr.title))
-            .collect()
-    } else {
-        prs
+/// The stages of the fetch of a pull request. The fields of a pull request are known from a stage on,
+/// so the filters run as soon as possible, before the expensive queries of the next stages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// The fields of the search results: title, author, size, merge state and the first page of reviews
+    Search,
+    /// The last commit and the rules of the base branch: CI, age and required approvals
+    Commit,
+    /// All the pages of review threads, reviews, labels and review requests
+    Connections,
+    /// The authors of the changed files, with --blame
+    Blame,
+}
+
+/// A filter on the pull requests, applied while they are fetched
+pub trait Filter: Send + Sync {
+    /// The name shown in the statistics of --debug
+    fn name(&self) -> String;
+    /// The first stage with the fields needed by the filter
+    fn stage(&self) -> Stage;
+    fn keep(&self, pr: &Pr) -> bool;
+}
+
+/// The filters applied in order to every pull request, at every stage.
+/// It counts the pull requests excluded by each filter.
+pub struct Pipeline {
+    filters: Vec<Box<dyn Filter>>,
+    excluded: Vec<AtomicUsize>,
+    checked: AtomicUsize,
+    // The URLs of the pull requests already seen
+    seen: Mutex<HashSet<String>>,
+}
+
+impl Pipeline {
+    pub fn new(filters: Vec<Box<dyn Filter>>) -> Self {
+        let excluded = filters.iter().map(|_| AtomicUsize::new(0)).collect();
+        Pipeline {
+            filters,
+            excluded,
+            checked: AtomicUsize::new(0),
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// True the first time a pull request is seen. The same pull request can be returned
+    /// by more than one search, and it goes through the filters once.
+    pub fn first_seen(&self, url: &str) -> bool {
+        self.seen
+            .lock()
+            .expect("not poisoned")
+            .insert(url.to_string())
+    }
+
+    /// Apply the filters of the stage and of the previous ones. The filters of the previous stages
+    /// run again, as some fields are complete only later, like the reviews checked for reviewed_by_me.
+    pub fn keep(&self, pr: &Pr, stage: Stage) -> bool {
+        if stage == Stage::Search {
+            self.checked.fetch_add(1, Ordering::Relaxed);
+        }
+        match self
+            .filters
+            .iter()
+            .position(|filter| filter.stage() <= stage && !filter.keep(pr))
+        {
+            Some(index) => {
+                self.excluded[index].fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// How many pull requests went through the filters
+    pub fn checked(&self) -> usize {
+        self.checked.load(Ordering::Relaxed)
+    }
+
+    /// How many pull requests each filter excluded
+    pub fn stats(&self) -> String {
+        let checked = self.checked.load(Ordering::Relaxed);
+        let excluded: Vec<usize> = self
+            .excluded
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        let mut stats = format!(
+            ">> Filters: {checked} pull requests, {} kept",
+            checked - excluded.iter().sum::<usize>()
+        );
+        for (filter, excluded) in self.filters.iter().zip(excluded) {
+            write!(stats, "\n>>   {}: {excluded} excluded", filter.name()).unwrap();
+        }
+        stats
+    }
+}
+
+/// Exclude the pull requests without changes
+pub struct NotEmpty;
+
+impl Filter for NotEmpty {
+    fn name(&self) -> String {
+        "empty".to_string()
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Search
+    }
+
+    fn keep(&self, pr: &Pr) -> bool {
+        pr.additions != 0 || pr.deletions != 0
+    }
+}
+
+/// Select the pull requests with a title matching (--regex) or not matching (--regex-not) a regex
+pub struct Title {
+    re: Regex,
+    matching: bool,
+}
+
+impl Title {
+    pub fn new(regex_text: &str, matching: bool) -> Result<Self> {
+        let option = if matching { "--regex" } else { "--regex-not" };
+        Ok(Title {
+            re: Regex::new(regex_text)
+                .map_err(|e| anyhow!("Invalid {option} {regex_text:?}: {e}"))?,
+            matching,
+        })
     }
 }
 
+impl Filter for Title {
+    fn name(&self) -> String {
+        if self.matching {
+            format!("--regex {}", self.re)
+        } else {
+            format!("--regex-not {}", self.re)
+        }
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Search
+    }
+
+    fn keep(&self, pr: &Pr) -> bool {
+        self.re.is_match(&pr.title) == self.matching
+    }
+}
+
+/// Exclude the pull requests I have reviewed. At the search stage only the first page of reviews is known:
+/// it excludes the pull requests reviewed there, and the others are checked again with all the reviews.
+pub struct NotReviewedByMe;
+
+impl Filter for NotReviewedByMe {
+    fn name(&self) -> String {
+        "reviewed by me".to_string()
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Search
+    }
+
+    fn keep(&self, pr: &Pr) -> bool {
+        !pr.reviewed_by_me
+    }
+}
+
+/// A filter written in the --where expression language
+pub struct Condition {
+    name: &'static str,
+    expr: Expr,
+}
+
+impl Condition {
+    fn new(name: &'static str, conditions: &[String]) -> Result<Option<Self>> {
+        if conditions.is_empty() {
+            return Ok(None);
+        }
+        let expr = conditions
+            .join(" && ")
+            .parse()
+            .map_err(|e| anyhow!("Invalid {name} filter: {e}"))?;
+        Ok(Some(Condition { name, expr }))
+    }
+}
+
+impl Filter for Condition {
+    fn name(&self) -> String {
+        format!("{} ({})", self.name, self.expr)
+    }
+
+    fn stage(&self) -> Stage {
+        self.expr.stage()
+    }
+
+    fn keep(&self, pr: &Pr) -> bool {
+        self.expr.matches(pr)
+    }
+}
+
+/// The filters selected by the options, in the order they are applied
+pub fn filters(username: &str, options: &PrArgs) -> Result<Vec<Box<dyn Filter>>> {
+    let mut filters: Vec<Box<dyn Filter>> = vec![Box::new(NotEmpty)];
+    if let Some(regex) = &options.regex {
+        filters.push(Box::new(Title::new(regex, true)?));
+    }
+    if let Some(regex_not) = &options.regex_not {
+        filters.push(Box::new(Title::new(regex_not, false)?));
+    }
+    let conditions = [
+        ("tests state", tests_state_conditions(options)),
+        (
+            "conflicts",
+            if options.include_conflicts {
+                vec![]
+            } else {
+                vec!["merge != \"conflict\"".to_string()]
+            },
+        ),
+        (
+            "mine",
+            if options.include_mine || options.only_mine {
+                vec![]
+            } else {
+                vec![format!("author != {}", quote(username))]
+            },
+        ),
+        ("size", size_conditions(options)),
+        ("age", age_conditions(options)),
+        (
+            "team",
+            options
+                .team
+                .iter()
+                .map(|team| format!("team == {}", quote(team)))
+                .collect(),
+        ),
+    ];
+    for (name, conditions) in conditions {
+        if let Some(condition) = Condition::new(name, &conditions)? {
+            filters.push(Box::new(condition));
+        }
+    }
+    if !options.include_reviewed_by_me && !options.only_mine {
+        filters.push(Box::new(NotReviewedByMe));
+    }
+    if let Some(where_expr) = &options.where_expr {
+        for expr in where_expr.conjuncts() {
+            filters.push(Box::new(Condition {
+                name: "--where",
+                expr,
+            }));
+        }
+    }
+    Ok(filters)
+}
+
+fn tests_state_conditions(options: &PrArgs) -> Vec<String> {
+    [
+        ("pending", !options.include_tests_pending),
+        ("fail", !options.include_tests_failure),
+        ("miss", !options.include_tests_missing),
+        ("none", options.exclude_tests_none),
+        ("ok", options.exclude_tests_success),
+    ]
+    .into_iter()
+    .filter(|(_, excluded)| *excluded)
+    .map(|(ci, _)| format!("ci != \"{ci}\""))
+    .collect()
+}
+
+fn size_conditions(options: &PrArgs) -> Vec<String> {
+    [
+        ("lines <=", options.max_lines),
+        ("lines >=", options.min_lines),
+        ("files <=", options.max_files),
+    ]
+    .into_iter()
+    .filter_map(|(condition, limit)| limit.map(|limit| format!("{condition} {limit}")))
+    .collect()
+}
+
+fn age_conditions(options: &PrArgs) -> Vec<String> {
+    [("age >", options.older_than), ("age <", options.newer_than)]
+        .into_iter()
+        .filter_map(|(condition, limit)| {
+            limit.map(|limit| format!("{condition} {}", duration(limit)))
+        })
+        .collect()
+}
+
+/// A duration literal of the expression language, in the largest exact unit
+fn duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    [("w", 7 * 24 * 60), ("d", 24 * 60), ("h", 60)]
+        .into_iter()
+        .find(|(_, unit)| minutes != 0 && minutes % unit == 0)
+        .map_or_else(
+            || format!("{minutes}m"),
+            |(name, unit)| format!("{}{name}", minutes / unit),
+        )
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::types::*;
     use super::*;
 
-    fn make_scored_pr(title: &str, url: &str) -> ScoredPr {
-        let pr = Pr {
+    fn make_pr(title: &str, url: &str) -> Pr {
+        Pr {
             title: title.to_string(),
             url: url.to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
-            num_approvals: 0,
-            num_reviewers: 0,
-            additions: 0,
-            deletions: 0,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
-        };
-        let score = Score::from_pr(1, &pr);
-        ScoredPr { pr, score }
+            ..test_pr()
+        }
+    }
+
+    fn make_options(args: &[&str]) -> PrArgs {
+        use clap::Parser;
+        let mut argv = vec!["ateam", "pr"];
+        argv.extend_from_slice(args);
+        match super::super::cli::Ateam::parse_from(argv).cmd {
+            super::super::cli::Command::Pr(options) => options,
+            super::super::cli::Command::Followup(_) | super::super::cli::Command::Tui(_) => {
+                unreachable!()
+            }
+        }
+    }
+
+    fn filter_titles(regex_text: Option<&str>, prs: Vec<Pr>) -> Vec<Pr> {
+        let pipeline = Pipeline::new(match regex_text {
+            Some(regex_text) => vec![Box::new(Title::new(regex_text, true).unwrap())],
+            None => vec![],
+        });
+        prs.into_iter()
+            .filter(|pr| pipeline.keep(pr, Stage::Search))
+            .collect()
+    }
+
+    fn names(filters: &[Box<dyn Filter>]) -> Vec<String> {
+        filters.iter().map(|filter| filter.name()).collect()
     }
 
     #[test]
     fn test_regex_none_returns_all() {
         let prs = vec![
-            make_scored_pr("Add feature", "https://example.com/1"),
-            make_scored_pr("Fix bug", "https://example.com/2"),
+            make_pr("Add feature", "https://example.com/1"),
+            make_pr("Fix bug", "https://example.com/2"),
         ];
-        let regex_text: Option<String> = None;
-        let result = filter_prs(regex_text.as_ref(), prs);
+        let result = filter_titles(None, prs);
         assert_eq!(result.len(), 2);
     }
 
     #[test]
     fn test_regex_matching() {
         let prs = vec![
-            make_scored_pr("Add feature", "https://example.com/1"),
-            make_scored_pr("Fix bug", "https://example.com/2"),
-            make_scored_pr("Add tests", "https://example.com/3"),
+            make_pr("Add feature", "https://example.com/1"),
+            make_pr("Fix bug", "https://example.com/2"),
+            make_pr("Add tests", "https://example.com/3"),
         ];
-        let regex_text = Some("Add".to_string());
-        let result = filter_prs(regex_text.as_ref(), prs);
+        let result = filter_titles(Some("Add"), prs);
         assert_eq!(result.len(), 2);
-        assert!(result.iter().any(|pr| pr.pr.url == "https://example.com/1"));
-        assert!(result.iter().any(|pr| pr.pr.url == "https://example.com/3"));
+        assert!(result.iter().any(|pr| pr.url == "https://example.com/1"));
+        assert!(result.iter().any(|pr| pr.url == "https://example.com/3"));
     }
 
     #[test]
     fn test_regex_no_match() {
         let prs = vec![
-            make_scored_pr("Add feature", "https://example.com/1"),
-            make_scored_pr("Fix bug", "https://example.com/2"),
+            make_pr("Add feature", "https://example.com/1"),
+            make_pr("Fix bug", "https://example.com/2"),
         ];
-        let regex_text = Some("delete".to_string());
-        let result = filter_prs(regex_text.as_ref(), prs);
+        let result = filter_titles(Some("delete"), prs);
         assert_eq!(result.len(), 0);
     }
 
     #[test]
     fn test_regex_case_sensitive() {
         let prs = vec![
-            make_scored_pr("Add Feature", "https://example.com/1"),
-            make_scored_pr("add feature", "https://example.com/2"),
+            make_pr("Add Feature", "https://example.com/1"),
+            make_pr("add feature", "https://example.com/2"),
         ];
-        let regex_text = Some("Add".to_string());
-        let result = filter_prs(regex_text.as_ref(), prs);
+        let result = filter_titles(Some("Add"), prs);
         assert_eq!(result.len(), 1);
-        assert_eq!(result[0].pr.url, "https://example.com/1");
+        assert_eq!(result[0].url, "https://example.com/1");
     }
 
     #[test]
     fn test_regex_empty_result() {
-        let prs: Vec<ScoredPr> = vec![];
-        let regex_text = Some("feature".to_string());
-        let result = filter_prs(regex_text.as_ref(), prs);
+        let result = filter_titles(Some("feature"), vec![]);
         assert_eq!(result.len(), 0);
     }
 
     #[test]
     fn test_regex_complex_pattern() {
         let prs = vec![
-            make_scored_pr("FIX-123: Fix bug", "https://example.com/1"),
-            make_scored_pr("FEAT-456: Add feature", "https://example.com/2"),
-            make_scored_pr("Update docs", "https://example.com/3"),
+            make_pr("FIX-123: Fix bug", "https://example.com/1"),
+            make_pr("FEAT-456: Add feature", "https://example.com/2"),
+            make_pr("Update docs", "https://example.com/3"),
         ];
-        let regex_text = Some("(FIX|FEAT)".to_string());
-        let result = filter_prs(regex_text.as_ref(), prs);
+        let result = filter_titles(Some("(FIX|FEAT)"), prs);
         assert_eq!(result.len(), 2);
     }
+
+    #[test]
+    fn test_regex_not() {
+        let title = Title::new("WIP", false).unwrap();
+        assert!(title.keep(&make_pr("Add feature", "https://example.com/1")));
+        assert!(!title.keep(&make_pr("WIP: Add feature", "https://example.com/2")));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let error = filters("me", &make_options(&["--regex", "(unclosed"]))
+            .err()
+            .unwrap();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Invalid --regex \"(unclosed\"")
+        );
+        assert!(filters("me", &make_options(&["--regex-not", "["])).is_err());
+    }
+
+    #[test]
+    fn test_filters_defaults() {
+        let filters = filters("me", &make_options(&[])).unwrap();
+        assert_eq!(
+            names(&filters),
+            vec![
+                "empty",
+                "tests state (ci != \"pending\" && ci != \"fail\" && ci != \"miss\")",
+                "conflicts (merge != \"conflict\")",
+                "mine (author != \"me\")",
+                "reviewed by me",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filters_options() {
+        let filters = filters(
+            "me",
+            &make_options(&[
+                "--include-tests-pending",
+                "--include-tests-failure",
+                "--include-tests-missing",
+                "--exclude-tests-success",
+                "--include-conflicts",
+                "--only-mine",
+                "--team",
+                "backend",
+                "--max-lines",
+                "400",
+                "--min-lines",
+                "10",
+                "--max-files",
+                "5",
+                "--older-than",
+                "2d",
+                "--newer-than",
+                "90m",
+                "--regex",
+                "fix",
+                "--where",
+                "additions < 400 || codeowner",
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            names(&filters),
+            vec![
+                "empty",
+                "--regex fix",
+                "tests state (ci != \"ok\")",
+                "size (lines <= 400 && lines >= 10 && files <= 5)",
+                "age (age > 2d && age < 90m)",
+                "team (team == \"backend\")",
+                "--where (additions < 400 || codeowner)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_where_conjuncts() {
+        let filters = filters(
+            "me",
+            &make_options(&[
+                "--include-reviewed-by-me",
+                "--where",
+                "blame && ci != \"fail\" && (team == \"backend\" || codeowner) && title ~ \"fix\"",
+            ]),
+        )
+        .unwrap();
+        let where_filters: Vec<(String, Stage)> = filters
+            .iter()
+            .filter(|filter| filter.name().starts_with("--where"))
+            .map(|filter| (filter.name(), filter.stage()))
+            .collect();
+        assert_eq!(
+            where_filters,
+            vec![
+                ("--where (blame)".to_string(), Stage::Blame),
+                ("--where (ci != \"fail\")".to_string(), Stage::Commit),
+                (
+                    "--where ((team == \"backend\" || codeowner))".to_string(),
+                    Stage::Connections
+                ),
+                ("--where (title ~ \"fix\")".to_string(), Stage::Search),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tests_state_before_connections() {
+        let pipeline = Pipeline::new(filters("me", &make_options(&["--team", "backend"])).unwrap());
+        let mut failing = make_pr("Fix bug", "https://example.com/1");
+        failing.tests_result = TestsState::Failure;
+        assert!(pipeline.keep(&failing, Stage::Search));
+        assert!(!pipeline.keep(&failing, Stage::Commit));
+        assert!(pipeline.stats().contains(
+            "tests state (ci != \"pending\" && ci != \"fail\" && ci != \"miss\"): 1 excluded"
+        ));
+    }
+
+    #[test]
+    fn test_first_seen() {
+        let pipeline = Pipeline::new(vec![]);
+        assert!(pipeline.first_seen("https://example.com/1"));
+        assert!(pipeline.first_seen("https://example.com/2"));
+        assert!(!pipeline.first_seen("https://example.com/1"));
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration(Duration::weeks(3)), "3w");
+        assert_eq!(duration(Duration::days(2)), "2d");
+        assert_eq!(duration(Duration::hours(6)), "6h");
+        assert_eq!(duration(Duration::minutes(90)), "90m");
+        assert_eq!(duration(Duration::zero()), "0m");
+    }
+
+    #[test]
+    fn test_size_and_age() {
+        let options = make_options(&["--max-lines", "100", "--older-than", "1d"]);
+        let pipeline = Pipeline::new(filters("me", &options).unwrap());
+        let mut small_old = make_pr("Small", "https://example.com/1");
+        small_old.last_commit_age_min = Some(2 * 24 * 60);
+        let mut big_old = small_old.clone();
+        big_old.additions = 200;
+        let mut small_new = small_old.clone();
+        small_new.last_commit_age_min = Some(60);
+        assert!(pipeline.keep(&small_old, Stage::Blame));
+        assert!(!pipeline.keep(&big_old, Stage::Blame));
+        assert!(!pipeline.keep(&small_new, Stage::Blame));
+    }
+
+    #[test]
+    fn test_stages() {
+        let options = make_options(&["--older-than", "1d", "--team", "backend"]);
+        let filters = filters("me", &options).unwrap();
+        assert_eq!(
+            filters
+                .iter()
+                .map(|filter| filter.stage())
+                .collect::<Vec<Stage>>(),
+            vec![
+                Stage::Search,
+                Stage::Commit,
+                Stage::Search,
+                Stage::Search,
+                Stage::Commit,
+                Stage::Connections,
+                Stage::Search,
+            ]
+        );
+
+        // The age is not known at the search stage: the pull request is kept until the commit stage
+        let pipeline = Pipeline::new(filters);
+        let mut new = make_pr("New", "https://example.com/1");
+        new.last_commit_age_min = Some(60);
+        assert!(pipeline.keep(&new, Stage::Search));
+        assert!(!pipeline.keep(&new, Stage::Commit));
+    }
+
+    #[test]
+    fn test_pipeline_stats() {
+        let pipeline = Pipeline::new(vec![
+            Box::new(NotEmpty),
+            Box::new(NotReviewedByMe),
+            Box::new(Title::new("Fix", true).unwrap()),
+        ]);
+        let mut empty = make_pr("Fix bug", "https://example.com/1");
+        empty.additions = 0;
+        let mut reviewed = make_pr("Fix bug", "https://example.com/2");
+        reviewed.reviewed_by_me = true;
+        let prs = vec![
+            empty,
+            reviewed,
+            make_pr("Add feature", "https://example.com/3"),
+            make_pr("Fix typo", "https://example.com/4"),
+        ];
+        let kept: Vec<Pr> = prs
+            .into_iter()
+            .filter(|pr| pipeline.keep(pr, Stage::Search))
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(
+            pipeline.stats(),
+            ">> Filters: 4 pull requests, 1 kept\n>>   empty: 1 excluded\n>>   reviewed by me: 1 excluded\n>>   --regex Fix: 1 excluded"
+        );
+    }
 }
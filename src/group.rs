@@ -0,0 +1,161 @@
+use super::cli::GroupBy;
+use super::types::{MergeState, ScoredPr, TestsState};
+use serde::Serialize;
+
+/// The pull requests of a group, in the order of the ranking
+#[derive(Debug, Serialize)]
+pub struct Group {
+    pub name: String,
+    pub count: usize,
+    pub pull_requests: Vec<ScoredPr>,
+}
+
+/// Group the sorted pull requests. The groups are in the order of their best pull request.
+/// A pull request with more than one label is in the group of each of them.
+pub fn groups(sprs: &[ScoredPr], by: GroupBy) -> Vec<Group> {
+    let mut groups: Vec<Group> = vec![];
+    for spr in sprs {
+        for name in keys(spr, by) {
+            match groups
+                .iter_mut()
+                .find(|group| group.name.eq_ignore_ascii_case(&name))
+            {
+                Some(group) => group.pull_requests.push(spr.clone()),
+                None => groups.push(Group {
+                    name,
+                    count: 0,
+                    pull_requests: vec![spr.clone()],
+                }),
+            }
+        }
+    }
+    for group in &mut groups {
+        group.count = group.pull_requests.len();
+    }
+    groups
+}
+
+fn keys(spr: &ScoredPr, by: GroupBy) -> Vec<String> {
+    let pr = &spr.pr;
+    match by {
+        GroupBy::Repo => vec![pr.repo.clone()],
+        GroupBy::Author => vec![pr.author.clone()],
+        GroupBy::Team => vec![pr.team.clone().unwrap_or_else(|| "No team".to_string())],
+        GroupBy::Label if pr.labels.0.is_empty() => vec!["No label".to_string()],
+        GroupBy::Label => pr.labels.0.iter().map(|label| label.name.clone()).collect(),
+        GroupBy::Attention => vec![attention(spr).to_string()],
+    }
+}
+
+/// What the pull request needs from me
+fn attention(spr: &ScoredPr) -> &'static str {
+    let pr = &spr.pr;
+    if matches!(pr.merge_state, MergeState::Conflicting)
+        || matches!(pr.tests_result, TestsState::Failure)
+    {
+        "Blocked on the author"
+    } else if matches!(pr.tests_result, TestsState::Pending | TestsState::Missing) {
+        "Waiting for CI"
+    } else if pr.requested {
+        "Review requested"
+    } else if pr.codeowner {
+        "Code owner"
+    } else {
+        "Ready for review"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::*;
+    use super::*;
+
+    fn make_scored_pr(
+        repo: &str,
+        labels: &[&str],
+        tests_result: TestsState,
+        requested: bool,
+    ) -> ScoredPr {
+        test_scored_pr(Pr {
+            title: format!("{repo} {}", labels.join(" ")),
+            url: format!("https://github.com/{repo}/pull/1"),
+            repo: repo.to_string(),
+            tests_result: tests_result.clone(),
+            all_tests_result: tests_result,
+            labels: Labels(
+                labels
+                    .iter()
+                    .map(|name| Label {
+                        name: (*name).to_string(),
+                        color: "ededed".to_string(),
+                    })
+                    .collect(),
+            ),
+            requested,
+            ..test_pr()
+        })
+    }
+
+    fn summary(groups: &[Group]) -> Vec<(String, usize)> {
+        groups
+            .iter()
+            .map(|group| (group.name.clone(), group.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_groups_by_repo() {
+        let sprs = vec![
+            make_scored_pr("acme/web", &[], TestsState::Success, false),
+            make_scored_pr("acme/api", &[], TestsState::Success, false),
+            make_scored_pr("Acme/Web", &[], TestsState::Success, false),
+        ];
+        let groups = groups(&sprs, GroupBy::Repo);
+        assert_eq!(
+            summary(&groups),
+            vec![("acme/web".to_string(), 2), ("acme/api".to_string(), 1)]
+        );
+        assert_eq!(groups[0].pull_requests[1].pr.repo, "Acme/Web");
+    }
+
+    #[test]
+    fn test_groups_by_label() {
+        let sprs = vec![
+            make_scored_pr("acme/web", &["bug", "ui"], TestsState::Success, false),
+            make_scored_pr("acme/api", &[], TestsState::Success, false),
+            make_scored_pr("acme/api", &["ui"], TestsState::Success, false),
+        ];
+        assert_eq!(
+            summary(&groups(&sprs, GroupBy::Label)),
+            vec![
+                ("bug".to_string(), 1),
+                ("ui".to_string(), 2),
+                ("No label".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_groups_by_attention() {
+        let sprs = vec![
+            make_scored_pr("acme/web", &[], TestsState::Success, true),
+            make_scored_pr("acme/api", &[], TestsState::Failure, true),
+            make_scored_pr("acme/cli", &[], TestsState::Pending, false),
+            make_scored_pr("acme/doc", &[], TestsState::Success, false),
+        ];
+        assert_eq!(
+            summary(&groups(&sprs, GroupBy::Attention)),
+            vec![
+                ("Review requested".to_string(), 1),
+                ("Blocked on the author".to_string(), 1),
+                ("Waiting for CI".to_string(), 1),
+                ("Ready for review".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_groups_empty() {
+        assert!(groups(&[], GroupBy::Team).is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+use super::table::{merge_state_label, show_bool, show_duration, tests_result_label};
+use super::types::{Label, Review, Score, ScoredPr, Summary, TestsState};
+use std::fmt::Write;
+
+#[cfg(test)]
+use super::types::{Labels, Pr, ReviewState, test_pr, test_scored_pr};
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em; color: #1f2328; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.3em; margin-top: 2em; }
+.summary { color: #59636e; }
+.summary code { background: #f6f8fa; padding: 0.1em 0.3em; border-radius: 4px; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border-bottom: 1px solid #d1d9e0; padding: 0.4em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; white-space: nowrap; }
+table.sortable th { cursor: pointer; user-select: none; }
+table.sortable th::after { content: " \2195"; color: #9198a1; }
+table.sortable th.asc::after { content: " \2191"; }
+table.sortable th.desc::after { content: " \2193"; }
+td.num { text-align: right; white-space: nowrap; }
+a { color: #0969da; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.label { display: inline-block; padding: 0 0.6em; margin: 0.1em; border-radius: 2em; font-size: 0.8em; font-weight: 500; }
+.ci { display: inline-block; padding: 0.1em 0.5em; border-radius: 4px; font-size: 0.8em; font-weight: 600; color: #fff; }
+.ci-ok { background: #1a7f37; }
+.ci-pending { background: #9a6700; }
+.ci-fail { background: #d1242f; }
+.ci-miss { background: #8250df; }
+.ci-none { background: #818b98; }
+.failed { color: #d1242f; font-size: 0.85em; }
+details summary { cursor: pointer; }
+details table { width: auto; margin-top: 0.3em; font-size: 0.85em; }
+details td, details th { padding: 0.1em 0.5em; }
+"#;
+
+const SCRIPT: &str = r"
+document.querySelectorAll('table.sortable').forEach(function (table) {
+  table.querySelectorAll('thead th').forEach(function (th, column) {
+    th.addEventListener('click', function () {
+      var ascending = !th.classList.contains('asc');
+      table.querySelectorAll('thead th').forEach(function (other) { other.classList.remove('asc', 'desc'); });
+      th.classList.add(ascending ? 'asc' : 'desc');
+      var body = table.tBodies[0];
+      var rows = Array.prototype.slice.call(body.rows);
+      var key = function (row) {
+        var cell = row.cells[column];
+        var value = cell.getAttribute('data-sort');
+        return value === null ? cell.textContent.trim().toLowerCase() : parseFloat(value);
+      };
+      rows.sort(function (a, b) {
+        var x = key(a), y = key(b);
+        var order = x < y ? -1 : x > y ? 1 : 0;
+        return ascending ? order : -order;
+      });
+      rows.forEach(function (row) { body.appendChild(row); });
+    });
+  });
+});
+";
+
+/// A single self-contained HTML page: no external style sheets, scripts or images.
+/// The pull requests section is omitted if `prs` is None, the reviews section if `reviews` is None.
+pub fn report(
+    prs: Option<(&[ScoredPr], usize)>,
+    reviews: Option<&[Review]>,
+    summary: Option<&Summary>,
+) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    html.push_str("<title>ateam review queue</title>\n");
+    let _ = write!(html, "<style>{STYLE}</style>\n</head>\n<body>\n");
+    html.push_str("<h1>Review queue</h1>\n");
+    if let Some(summary) = summary {
+        html.push_str(&summary_section(summary));
+    }
+    if let Some((sprs, limit)) = prs {
+        html.push_str(&prs_section(sprs, limit));
+    }
+    if let Some(reviews) = reviews {
+        html.push_str(&reviews_section(reviews));
+    }
+    let _ = write!(html, "<script>{SCRIPT}</script>\n</body>\n</html>\n");
+    html
+}
+
+fn summary_section(summary: &Summary) -> String {
+    let mut html = String::from("<ul class=\"summary\">\n");
+    for query in &summary.queries {
+        let _ = writeln!(html, "<li>Query: <code>{}</code></li>", escape(query));
+    }
+    let _ = writeln!(html, "<li>User: {}</li>", escape(&summary.user));
+    let _ = writeln!(
+        html,
+        "<li>Generated: {}</li>",
+        summary.generated_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    let _ = writeln!(html, "<li>Total: {}</li>", summary.total);
+    html.push_str("</ul>\n");
+    html
+}
+
+fn prs_section(sprs: &[ScoredPr], limit: usize) -> String {
+    let mut html = String::from("<h2>Pull requests</h2>\n<table class=\"sortable\">\n<thead><tr>");
+    for column in [
+        "#",
+        "Pull request",
+        "Repository",
+        "Author",
+        "Age",
+        "CI",
+        "O.C.",
+        "Appr.",
+        "Diff",
+        "On Main",
+        "Merge",
+        "Blame",
+        "Req.",
+        "C. Owner",
+        "Team",
+        "Score",
+    ] {
+        let _ = write!(html, "<th>{column}</th>");
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for (rank, spr) in sprs.iter().take(limit).enumerate() {
+        html.push_str(&pr_row(rank + 1, spr));
+    }
+    html.push_str("</tbody>\n</table>\n");
+    html
+}
+
+fn pr_row(rank: usize, spr: &ScoredPr) -> String {
+    let pr = &spr.pr;
+    let mut title = format!("<a href=\"{}\">{}</a>", escape(&pr.url), escape(&pr.title));
+    for label in &pr.labels.0 {
+        title.push(' ');
+        title.push_str(&label_chip(label));
+    }
+    if !pr.failed_checks.is_empty() {
+        let _ = write!(
+            title,
+            "<div class=\"failed\">Failed: {}</div>",
+            escape(&pr.failed_checks.join(", "))
+        );
+    }
+    let cells = [
+        num_cell(rank, &rank.to_string()),
+        format!("<td>{title}</td>"),
+        format!("<td>{}</td>", escape(&pr.repo)),
+        format!("<td>{}</td>", escape(&pr.author)),
+        num_cell(
+            pr.last_commit_age_min.unwrap_or(-1),
+            show_duration(pr.last_commit_age_min).trim_end(),
+        ),
+        format!("<td>{}</td>", ci_badge(&pr.tests_result)),
+        num_cell(pr.open_conversations, &pr.open_conversations.to_string()),
+        num_cell(
+            pr.num_approvals,
+            &format!("{}/{}", pr.num_approvals, pr.num_reviewers),
+        ),
+        num_cell(
+            pr.additions + pr.deletions,
+            &format!("+{} -{}", pr.additions, pr.deletions),
+        ),
+        format!("<td>{}</td>", show_bool(pr.based_on_main_branch)),
+        format!("<td>{}</td>", merge_state_label(&pr.merge_state)),
+        format!("<td>{}</td>", show_bool(pr.blame)),
+        format!("<td>{}</td>", show_bool(pr.requested)),
+        format!("<td>{}</td>", show_bool(pr.codeowner)),
+        format!("<td>{}</td>", escape(pr.team.as_deref().unwrap_or("-"))),
+        format!(
+            "<td class=\"num\" data-sort=\"{:.1}\">{}</td>",
+            spr.score.total(),
+            score_breakdown(&spr.score)
+        ),
+    ];
+    format!("<tr>{}</tr>\n", cells.concat())
+}
+
+fn num_cell<T: std::fmt::Display>(sort: T, text: &str) -> String {
+    format!(
+        "<td class=\"num\" data-sort=\"{sort}\">{}</td>",
+        escape(text)
+    )
+}
+
+/// The total score, expandable to the factors
+fn score_breakdown(score: &Score) -> String {
+    let mut html = format!("<details><summary>{:.1}</summary><table>", score.total());
+    for (name, value) in score.factors() {
+        let _ = write!(
+            html,
+            "<tr><td>{name}</td><td class=\"num\">{value:.1}</td></tr>"
+        );
+    }
+    html.push_str("</table></details>");
+    html
+}
+
+fn ci_badge(tests_result: &TestsState) -> String {
+    let class = match tests_result {
+        TestsState::Success => "ok",
+        TestsState::Pending => "pending",
+        TestsState::Failure => "fail",
+        TestsState::Missing => "miss",
+        TestsState::None => "none",
+    };
+    format!(
+        "<span class=\"ci ci-{class}\">{}</span>",
+        escape(tests_result_label(tests_result))
+    )
+}
+
+/// A chip with the color of the GitHub label and a readable text color
+fn label_chip(label: &Label) -> String {
+    let background = match label.rgb() {
+        Some((r, g, b)) => format!("#{r:02x}{g:02x}{b:02x}"),
+        None => "#ededed".to_string(),
+    };
+    let foreground = if label.is_light() {
+        "#1f2328"
+    } else {
+        "#ffffff"
+    };
+    format!(
+        "<span class=\"label\" style=\"background:{background};color:{foreground}\">{}</span>",
+        escape(&label.name)
+    )
+}
+
+fn reviews_section(reviews: &[Review]) -> String {
+    let mut html = String::from(
+        "<h2>Follow-up</h2>\n<table class=\"sortable\">\n<thead><tr><th>Review</th><th>State</th><th>Pull request</th></tr></thead>\n<tbody>\n",
+    );
+    for review in reviews {
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{}\">review</a></td><td>{}</td><td>{}</td></tr>",
+            escape(&review.url),
+            escape(&review.state.to_string()),
+            escape(&review.pr_title)
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+    html
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_scored_pr(title: &str, url: &str) -> ScoredPr {
+        test_scored_pr(Pr {
+            title: title.to_string(),
+            url: url.to_string(),
+            last_commit_age_min: Some(60),
+            tests_result: TestsState::Failure,
+            all_tests_result: TestsState::Failure,
+            failed_checks: vec!["lint".to_string()],
+            labels: Labels(vec![
+                Label {
+                    name: "bug".to_string(),
+                    color: "d73a4a".to_string(),
+                },
+                Label {
+                    name: "docs".to_string(),
+                    color: "fbca04".to_string(),
+                },
+            ]),
+            ..test_pr()
+        })
+    }
+
+    #[test]
+    fn test_report() {
+        let prs = vec![
+            make_scored_pr("Fix <script> & more", "https://example.com/1"),
+            make_scored_pr("Second", "https://example.com/2"),
+        ];
+        let reviews = vec![Review {
+            state: ReviewState::Dismissed,
+            url: "https://example.com/3".to_string(),
+            pr_title: "Reviewed".to_string(),
+        }];
+        let result = report(Some((&prs, 1)), Some(&reviews), None);
+        assert!(result.starts_with("<!DOCTYPE html>"));
+        assert!(
+            result.contains("<a href=\"https://example.com/1\">Fix &lt;script&gt; &amp; more</a>")
+        );
+        assert!(!result.contains("https://example.com/2"));
+        assert!(result.contains("<span class=\"ci ci-fail\">Fail</span>"));
+        assert!(result.contains("<div class=\"failed\">Failed: lint</div>"));
+        assert!(result.contains("<details><summary>"));
+        assert!(result.contains("<h2>Follow-up</h2>"));
+        assert!(result.contains("<a href=\"https://example.com/3\">review</a>"));
+        assert!(!result.contains("src="));
+        assert!(!result.contains("<link"));
+    }
+
+    #[test]
+    fn test_report_sections() {
+        let result = report(None, Some(&[]), None);
+        assert!(!result.contains("<h2>Pull requests</h2>"));
+        assert!(result.contains("<h2>Follow-up</h2>"));
+        let result = report(Some((&[], 10)), None, None);
+        assert!(result.contains("<h2>Pull requests</h2>"));
+        assert!(!result.contains("<h2>Follow-up</h2>"));
+    }
+
+    #[test]
+    fn test_label_chip() {
+        let chip = |color: &str| {
+            label_chip(&Label {
+                name: "a\"b".to_string(),
+                color: color.to_string(),
+            })
+        };
+        assert_eq!(
+            chip("d73a4a"),
+            "<span class=\"label\" style=\"background:#d73a4a;color:#ffffff\">a&quot;b</span>"
+        );
+        assert!(chip("fbca04").contains("color:#1f2328"));
+        assert!(chip("red\" onclick=\"x").contains("background:#ededed"));
+    }
+}
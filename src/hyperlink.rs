@@ -0,0 +1,78 @@
+use super::cli::When;
+use std::io::IsTerminal;
+
+/// True with --hyperlinks always, or with --hyperlinks auto if the output is a terminal known to support
+/// OSC 8 hyperlinks. The other terminals would print the escape sequences.
+pub fn detect(when: When) -> bool {
+    match when {
+        When::Always => true,
+        When::Never => false,
+        When::Auto => {
+            std::io::stdout().is_terminal() && supported(&|name| std::env::var(name).ok())
+        }
+    }
+}
+
+fn supported(env: &dyn Fn(&str) -> Option<String>) -> bool {
+    if env("TERM").as_deref() == Some("dumb") {
+        return false;
+    }
+    let term_program = env("TERM_PROGRAM").unwrap_or_default();
+    let term = env("TERM").unwrap_or_default();
+    matches!(
+        term_program.as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "Hyper" | "ghostty" | "rio"
+    ) || ["kitty", "alacritty", "foot", "wezterm", "ghostty"]
+        .iter()
+        .any(|name| term.contains(name))
+        || env("WT_SESSION").is_some()
+        || env("KONSOLE_VERSION").is_some()
+        // GNOME Terminal, Tilix and the other VTE terminals since 0.50
+        || env("VTE_VERSION")
+            .and_then(|version| version.parse::<u32>().ok())
+            .is_some_and(|version| version >= 5000)
+}
+
+/// The text as a link to the URL, with the OSC 8 escape sequence
+pub fn link(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supported_with(vars: &[(&str, &str)]) -> bool {
+        supported(&|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| (*value).to_string())
+        })
+    }
+
+    #[test]
+    fn test_supported() {
+        assert!(supported_with(&[("TERM_PROGRAM", "iTerm.app")]));
+        assert!(supported_with(&[("TERM", "xterm-kitty")]));
+        assert!(supported_with(&[("VTE_VERSION", "7006")]));
+        assert!(!supported_with(&[("VTE_VERSION", "4802")]));
+        assert!(supported_with(&[("WT_SESSION", "1234")]));
+        assert!(!supported_with(&[("TERM", "xterm-256color")]));
+        assert!(!supported_with(&[("TERM", "dumb"), ("WT_SESSION", "1")]));
+        assert!(!supported_with(&[]));
+    }
+
+    #[test]
+    fn test_link() {
+        assert_eq!(
+            link("https://example.com/1", "Fix bug"),
+            "\x1b]8;;https://example.com/1\x1b\\Fix bug\x1b]8;;\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(detect(When::Always));
+        assert!(!detect(When::Never));
+    }
+}
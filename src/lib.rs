@@ -1,49 +1,164 @@
 use anyhow::Result;
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::Mutex;
 
 mod client;
-use client::{fetch_scored_prs, followup, sorted_ranked_prs, username};
+use client::{fetch_scored_prs, followup, search_queries, sorted_ranked_prs, username};
 pub mod cli;
-use cli::{FollowupArgs, PrArgs};
+mod color;
+use cli::{FollowupArgs, Format, PrArgs};
+mod delimited;
+mod diversity;
+pub mod expr;
 mod filter;
+mod group;
+mod html;
+mod hyperlink;
+mod markdown;
 mod render;
 mod table;
+pub mod template;
+mod tui;
 mod types;
 
 #[allow(clippy::missing_errors_doc)]
 pub async fn pr(options: &PrArgs, github_api_token: &str) -> Result<Vec<types::ScoredPr>> {
-    let username = get_username(&options.user, github_api_token).await;
+    let username = get_username(&options.user, github_api_token).await?;
 
-    fetch_scored_prs(github_api_token, &username, options).await
+    Ok(
+        fetch_scored_prs(github_api_token, &username, options, None, true)
+            .await?
+            .prs,
+    )
 }
 
 #[allow(clippy::missing_errors_doc)]
 pub async fn pr_render(options: &PrArgs, github_api_token: &str) -> Result<String> {
-    let sprs: Vec<types::ScoredPr> = pr(options, github_api_token).await?;
-
-    Ok(render::prs(
-        &sorted_ranked_prs(sprs),
-        options.num,
-        options.debug,
-        options.short,
-        options.json,
-    ))
+    let username = get_username(&options.user, github_api_token).await?;
+    let format = options.format();
+
+    if render::streamed(options) {
+        return pr_stream(options, github_api_token, &username).await;
+    }
+
+    let fetched = fetch_scored_prs(github_api_token, &username, options, None, true).await?;
+    let summary = types::Summary {
+        queries: search_queries(&username, options),
+        user: username.clone(),
+        generated_at: chrono::Utc::now(),
+        fetched: fetched.fetched,
+        total: fetched.prs.len(),
+    };
+    let sprs = diversity::spread(sorted_ranked_prs(fetched.prs), options);
+
+    if let Some(template) = &options.template {
+        return render::templated_prs(&sprs, options.num, template);
+    }
+
+    let output = render::Output {
+        format,
+        num: options.num,
+        debug: options.debug,
+        header: !options.no_header,
+        summary: options.summary,
+        group_by: options.group_by,
+        style: table::Style {
+            columns: options.columns.clone(),
+            colors: color::Colors::detect(options.color),
+            hyperlinks: hyperlink::detect(options.hyperlinks),
+        },
+    };
+
+    if format == Format::Html {
+        let reviews = followup::followup(github_api_token, &username, options.debug).await?;
+        return Ok(render::report(&sprs, &output, &reviews, &summary));
+    }
+
+    render::prs(&sprs, &output, &summary)
 }
 
-pub async fn followup(options: &FollowupArgs, github_api_token: &str) -> Vec<types::Review> {
-    let username = get_username(&options.user, github_api_token).await;
+/// Print the pull requests as NDJSON while they are fetched.
+/// They can't be ranked before all of them are fetched.
+async fn pr_stream(options: &PrArgs, github_api_token: &str, username: &str) -> Result<String> {
+    let printed = Mutex::new(HashSet::new());
+    let error = Mutex::new(None);
+    let print = |spr: &types::ScoredPr| {
+        let mut printed = printed.lock().expect("not poisoned");
+        // The same pull request can be returned by more than one search
+        if !printed.insert(spr.pr.url.clone()) {
+            return;
+        }
+        match render::ndjson_line(spr) {
+            Ok(line) => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = writeln!(stdout, "{line}");
+                let _ = stdout.flush();
+            }
+            Err(e) => {
+                error.lock().expect("not poisoned").get_or_insert(e);
+            }
+        }
+    };
+    fetch_scored_prs(github_api_token, username, options, Some(&print), true).await?;
+    match error.into_inner().expect("not poisoned") {
+        Some(e) => Err(e),
+        None => Ok(String::new()),
+    }
+}
 
-    followup::followup(github_api_token, &username).await
+#[allow(clippy::missing_errors_doc)]
+pub async fn followup(
+    options: &FollowupArgs,
+    github_api_token: &str,
+) -> Result<Vec<types::Review>> {
+    let username = get_username(&options.user, github_api_token).await?;
+
+    followup::followup(github_api_token, &username, options.debug).await
+}
+
+#[allow(clippy::missing_errors_doc)]
+pub async fn followup_render(options: &FollowupArgs, github_api_token: &str) -> Result<String> {
+    let username = get_username(&options.user, github_api_token).await?;
+    let reviews = followup::followup(github_api_token, &username, options.debug).await?;
+    let summary = types::Summary {
+        queries: vec![followup::github_query(&username).to_string()],
+        user: username,
+        generated_at: chrono::Utc::now(),
+        fetched: reviews.len(),
+        total: reviews.len(),
+    };
+
+    if let Some(template) = &options.template {
+        return render::templated_reviews(&reviews, template);
+    }
+
+    let output = render::Output {
+        format: options.format(),
+        num: None,
+        debug: options.debug,
+        header: !options.no_header,
+        summary: options.summary,
+        group_by: None,
+        style: table::Style {
+            hyperlinks: hyperlink::detect(options.hyperlinks),
+            ..table::Style::default()
+        },
+    };
+    render::reviews(&reviews, &output, &summary)
 }
 
-pub async fn followup_render(options: &FollowupArgs, github_api_token: &str) -> String {
-    let reviews = followup(options, github_api_token).await;
+#[allow(clippy::missing_errors_doc)]
+pub async fn tui(options: &PrArgs, github_api_token: &str) -> Result<()> {
+    let username = get_username(&options.user, github_api_token).await?;
 
-    render::reviews(&reviews, options.json)
+    tui::run(options, github_api_token, &username).await
 }
 
-pub async fn get_username(user: &Option<String>, github_api_token: &str) -> String {
+#[allow(clippy::missing_errors_doc)]
+pub async fn get_username(user: &Option<String>, github_api_token: &str) -> Result<String> {
     match user {
-        Some(username) => username.clone(),
+        Some(username) => Ok(username.clone()),
         None => username::username(github_api_token).await,
     }
 }
@@ -0,0 +1,310 @@
+use super::group::Group;
+use super::table::{merge_state_label, show_bool, show_duration, tests_result_label};
+use super::types::{Label, Review, ScoredPr, Summary};
+use std::fmt::Write;
+
+#[cfg(test)]
+use super::types::{Labels, Pr, ReviewState, test_pr, test_scored_pr};
+
+/// GitHub-flavored markdown, for pasting into issues, pull requests and wikis
+pub fn from(sprs: &[ScoredPr], limit: usize, summary: Option<&Summary>) -> String {
+    let shown = sprs.len().min(limit);
+    let mut markdown = header(summary, "Pull requests", shown);
+    markdown.push_str(&table(&sprs[..shown]));
+    markdown
+}
+
+/// A section for each group, with the number of pull requests in the heading
+pub fn from_groups(groups: &[Group], shown: usize, summary: Option<&Summary>) -> String {
+    let mut markdown = header(summary, "Pull requests", shown);
+    let sections: Vec<String> = groups
+        .iter()
+        .map(|group| {
+            format!(
+                "### {} ({})\n\n{}",
+                escape(&group.name),
+                group.count,
+                table(&group.pull_requests)
+            )
+        })
+        .collect();
+    markdown.push_str(&sections.join("\n"));
+    markdown
+}
+
+fn table(sprs: &[ScoredPr]) -> String {
+    let mut markdown = String::from(
+        "| Pull request | Age | CI | O.C. | Appr. | Diff | On Main | Merge | Blame | Req. | C. Owner | Team | Score |\n",
+    );
+    markdown.push_str(
+        "| --- | --- | --- | ---: | ---: | --- | --- | --- | --- | --- | --- | --- | ---: |\n",
+    );
+    for spr in sprs {
+        markdown.push_str(&row(&pr_row(spr)));
+    }
+    markdown
+}
+
+pub fn from_reviews(reviews: &[Review], summary: Option<&Summary>) -> String {
+    let mut markdown = header(summary, "Reviews", reviews.len());
+    markdown.push_str("| Review | State | Pull request |\n");
+    markdown.push_str("| --- | --- | --- |\n");
+    for review in reviews {
+        markdown.push_str(&row(&[
+            link("review", &review.url),
+            escape(&review.state.to_string()),
+            escape(&review.pr_title),
+        ]));
+    }
+    markdown
+}
+
+fn header(summary: Option<&Summary>, what: &str, shown: usize) -> String {
+    let Some(summary) = summary else {
+        return String::new();
+    };
+    let mut header = String::new();
+    for query in &summary.queries {
+        let _ = writeln!(header, "- **Query:** {}", code(query));
+    }
+    let _ = writeln!(header, "- **User:** @{}", summary.user);
+    let _ = writeln!(
+        header,
+        "- **Generated:** {}",
+        summary.generated_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    let _ = writeln!(header, "- **{what}:** {shown} of {}\n", summary.total);
+    header
+}
+
+fn pr_row(spr: &ScoredPr) -> Vec<String> {
+    let pr = &spr.pr;
+    let mut title = link(&pr.title, &pr.url);
+    for label in &pr.labels.0 {
+        title.push(' ');
+        title.push_str(&badge(label));
+    }
+    if !pr.failed_checks.is_empty() {
+        let _ = write!(
+            title,
+            "<br>Failed: {}",
+            escape(&pr.failed_checks.join(", "))
+        );
+    }
+    vec![
+        title,
+        show_duration(pr.last_commit_age_min).trim_end().to_string(),
+        tests_result_label(&pr.tests_result).to_string(),
+        pr.open_conversations.to_string(),
+        format!("{}/{}", pr.num_approvals, pr.num_reviewers),
+        format!("+{} -{}", pr.additions, pr.deletions),
+        show_bool(pr.based_on_main_branch).to_string(),
+        merge_state_label(&pr.merge_state).to_string(),
+        show_bool(pr.blame).to_string(),
+        show_bool(pr.requested).to_string(),
+        show_bool(pr.codeowner).to_string(),
+        escape(pr.team.as_deref().unwrap_or("-")),
+        format!("{:.1}", spr.score.total()),
+    ]
+}
+
+fn row(cells: &[String]) -> String {
+    format!("| {} |\n", cells.join(" | "))
+}
+
+fn link(text: &str, url: &str) -> String {
+    format!(
+        "[{}]({})",
+        escape(text),
+        url.replace(' ', "%20").replace(')', "%29")
+    )
+}
+
+/// A shields.io badge with the name and the color of the label
+fn badge(label: &Label) -> String {
+    let name = label
+        .name
+        .replace('-', "--")
+        .replace('_', "__")
+        .replace(' ', "_");
+    format!(
+        "![{}](https://img.shields.io/badge/{}-{})",
+        escape(&label.name),
+        percent_encode(&name),
+        percent_encode(&label.color)
+    )
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
+
+/// Escape the characters with a meaning in markdown inline text and in table cells
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Inline code that can contain backticks
+fn code(text: &str) -> String {
+    let fence = "`".repeat(longest_backtick_run(text) + 1);
+    let text = text.replace('|', "\\|");
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{fence} {text} {fence}")
+    } else {
+        format!("{fence}{text}{fence}")
+    }
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn make_scored_pr(title: &str, url: &str, labels: Vec<Label>) -> ScoredPr {
+        test_scored_pr(Pr {
+            title: title.to_string(),
+            url: url.to_string(),
+            last_commit_age_min: Some(90),
+            open_conversations: 2,
+            num_approvals: 1,
+            num_reviewers: 2,
+            additions: 10,
+            deletions: 3,
+            based_on_main_branch: true,
+            labels: Labels(labels),
+            requested: true,
+            team: Some("backend".to_string()),
+            ..test_pr()
+        })
+    }
+
+    fn summary() -> Summary {
+        Summary {
+            queries: vec!["is:pr is:open org:acme".to_string()],
+            user: "alice".to_string(),
+            generated_at: Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap(),
+            fetched: 4,
+            total: 3,
+        }
+    }
+
+    #[test]
+    fn test_from_table() {
+        let prs = vec![make_scored_pr(
+            "Fix the | pipe",
+            "https://github.com/frisoft/ateam/pull/1",
+            vec![],
+        )];
+        let result = from(&prs, 10, None);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("| Pull request | Age |"));
+        assert!(lines[2].starts_with(
+            "| [Fix the \\| pipe](https://github.com/frisoft/ateam/pull/1) | 1h 30m | OK | 2 | 1/2 | +10 -3 | yes | clean | no | yes | no | backend |"
+        ));
+    }
+
+    #[test]
+    fn test_from_limit_and_summary() {
+        let prs = vec![
+            make_scored_pr("One", "https://example.com/1", vec![]),
+            make_scored_pr("Two", "https://example.com/2", vec![]),
+        ];
+        let result = from(&prs, 1, Some(&summary()));
+        assert!(result.starts_with(
+            "- **Query:** `is:pr is:open org:acme`\n- **User:** @alice\n- **Generated:** 2024-05-01 12:30 UTC\n- **Pull requests:** 1 of 3\n\n| Pull request"
+        ));
+        assert!(!result.contains("https://example.com/2"));
+    }
+
+    #[test]
+    fn test_from_groups() {
+        let groups = vec![
+            Group {
+                name: "acme/api".to_string(),
+                count: 2,
+                pull_requests: vec![
+                    make_scored_pr("One", "https://example.com/1", vec![]),
+                    make_scored_pr("Two", "https://example.com/2", vec![]),
+                ],
+            },
+            Group {
+                name: "acme/web".to_string(),
+                count: 1,
+                pull_requests: vec![make_scored_pr("Three", "https://example.com/3", vec![])],
+            },
+        ];
+        let result = from_groups(&groups, 3, None);
+        assert!(result.starts_with("### acme/api (2)\n\n| Pull request |"));
+        assert!(result.contains("\n\n### acme/web (1)\n\n| Pull request |"));
+        assert_eq!(result.matches("| [").count(), 3);
+    }
+
+    #[test]
+    fn test_badge() {
+        let label = Label {
+            name: "good first-issue".to_string(),
+            color: "7057ff".to_string(),
+        };
+        assert_eq!(
+            badge(&label),
+            "![good first-issue](https://img.shields.io/badge/good_first--issue-7057ff)"
+        );
+        let label = Label {
+            name: "a/b & c".to_string(),
+            color: "ededed".to_string(),
+        };
+        assert_eq!(
+            badge(&label),
+            "![a/b & c](https://img.shields.io/badge/a%2Fb_%26_c-ededed)"
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("*bold* [x] a|b\nc"), "\\*bold\\* \\[x\\] a\\|b c");
+    }
+
+    #[test]
+    fn test_code() {
+        assert_eq!(code("label:bug"), "`label:bug`");
+        assert_eq!(code("a `b` c"), "``a `b` c``");
+        assert_eq!(code("`a`"), "`` `a` ``");
+    }
+
+    #[test]
+    fn test_from_reviews() {
+        let reviews = vec![Review {
+            state: ReviewState::Dismissed,
+            url: "https://example.com/1#pullrequestreview-1".to_string(),
+            pr_title: "Fix bug".to_string(),
+        }];
+        let result = from_reviews(&reviews, None);
+        assert!(result.contains(
+            "| [review](https://example.com/1#pullrequestreview-1) | Dismissed | Fix bug |"
+        ));
+    }
+}
@@ -1,80 +1,375 @@
+use super::cli::{Format, GroupBy, PrArgs};
+use super::delimited;
+use super::diversity;
+use super::group::{self, Group};
+use super::html;
+use super::hyperlink;
+use super::markdown;
 use super::table;
+use super::template::Template;
 use super::types::Review;
-use super::types::ScoredPr;
-
-pub fn prs(sprs: &[ScoredPr], num: Option<usize>, debug: bool, short: bool, json: bool) -> String {
-    let limit = num.unwrap_or(10000);
-    if json {
-        json_prs(sprs, limit)
-    } else if short {
-        short_prs(sprs, limit, debug)
-    } else {
-        format!("{}", table::from(sprs, limit, debug))
+use super::types::{ScoredPr, Summary};
+use anyhow::Result;
+use serde::Serialize;
+
+/// The version of the JSON envelope. Increment it when a field is removed or changes meaning.
+const JSON_VERSION: u32 = 1;
+
+/// How the pull requests or the reviews are rendered
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub format: Format,
+    pub num: Option<usize>,
+    pub debug: bool,
+    /// The header row of the csv and tsv outputs
+    pub header: bool,
+    /// The summary header of the markdown and html outputs
+    pub summary: bool,
+    pub group_by: Option<GroupBy>,
+    pub style: table::Style,
+}
+
+impl Output {
+    fn limit(&self) -> usize {
+        self.num.unwrap_or(usize::MAX)
     }
+
+    fn summary<'a>(&self, summary: &'a Summary) -> Option<&'a Summary> {
+        self.summary.then_some(summary)
+    }
+}
+
+pub fn prs(sprs: &[ScoredPr], output: &Output, summary: &Summary) -> Result<String> {
+    let limit = output.limit();
+    if let Some(by) = output.group_by
+        && matches!(
+            output.format,
+            Format::Table | Format::Markdown | Format::Json
+        )
+    {
+        let sprs = &sprs[..limit.min(sprs.len())];
+        return grouped_prs(&group::groups(sprs, by), sprs.len(), output, summary);
+    }
+    Ok(match output.format {
+        Format::Json => json_prs(sprs, limit, summary)?,
+        Format::Ndjson => ndjson(sprs.iter().take(limit))?,
+        Format::Short => short_prs(sprs, limit, output.debug, &output.style),
+        Format::Markdown => markdown::from(sprs, limit, output.summary(summary)),
+        Format::Csv => delimited::from(sprs, limit, b',', output.header)?,
+        Format::Tsv => delimited::from(sprs, limit, b'\t', output.header)?,
+        Format::Html => html::report(Some((sprs, limit)), None, output.summary(summary)),
+        Format::Table => format!("{}", table::from(sprs, limit, output.debug, &output.style)),
+    })
+}
+
+fn grouped_prs(
+    groups: &[Group],
+    shown: usize,
+    output: &Output,
+    summary: &Summary,
+) -> Result<String> {
+    Ok(match output.format {
+        Format::Json => serde_json::to_string(&GroupsEnvelope {
+            version: JSON_VERSION,
+            metadata: Metadata::new(summary, shown),
+            groups,
+        })?,
+        Format::Markdown => markdown::from_groups(groups, shown, output.summary(summary)),
+        _ => groups
+            .iter()
+            .map(|group| {
+                format!(
+                    "{} ({})\n{}",
+                    group.name,
+                    group.count,
+                    table::from(
+                        &group.pull_requests,
+                        usize::MAX,
+                        output.debug,
+                        &output.style
+                    )
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+    })
 }
 
-fn short_prs(sprs: &[ScoredPr], limit: usize, debug: bool) -> String {
+fn short_prs(sprs: &[ScoredPr], limit: usize, debug: bool, style: &table::Style) -> String {
     sprs.iter()
         .take(limit)
-        .map(|spr| pr(spr, debug))
+        .map(|spr| pr(spr, debug, style))
         .collect::<Vec<String>>()
         .join("\n")
 }
 
-fn pr(spr: &ScoredPr, _debug: bool) -> String {
-    format!("{}", &spr.pr)
-}
-
-pub fn reviews(reviews: &[Review], json: bool) -> String {
-    if json {
-        json_reviews(reviews)
+fn pr(spr: &ScoredPr, _debug: bool, style: &table::Style) -> String {
+    if style.colors.is_none() && !style.hyperlinks {
+        return format!("{}", &spr.pr);
+    }
+    let labels = match style.colors {
+        Some(colors) => colors.labels(&spr.pr.labels),
+        None => spr.pr.labels.to_string(),
+    };
+    if style.hyperlinks {
+        format!("{} {labels}", hyperlink::link(&spr.pr.url, &spr.pr.title))
     } else {
-        format!("{}", table::from_reviews(reviews))
+        format!("{} - {} {labels}", spr.pr.url, spr.pr.title)
     }
 }
 
-fn json_prs(sprs: &[ScoredPr], limit: usize) -> String {
-    let len = sprs.len();
-    let l = if limit > len { len } else { limit };
-    match serde_json::to_string(&sprs[..l]) {
-        Ok(json) => json,
-        Err(error) => error.to_string(),
-    }
+pub fn reviews(reviews: &[Review], output: &Output, summary: &Summary) -> Result<String> {
+    Ok(match output.format {
+        Format::Json => json_reviews(reviews, summary)?,
+        Format::Ndjson => ndjson(reviews.iter())?,
+        Format::Markdown => markdown::from_reviews(reviews, output.summary(summary)),
+        Format::Csv => delimited::from_reviews(reviews, b',', output.header)?,
+        Format::Tsv => delimited::from_reviews(reviews, b'\t', output.header)?,
+        Format::Html => html::report(None, Some(reviews), output.summary(summary)),
+        Format::Table | Format::Short => {
+            format!("{}", table::from_reviews(reviews, output.style.hyperlinks))
+        }
+    })
+}
+
+pub fn templated_prs(sprs: &[ScoredPr], num: Option<usize>, template: &Template) -> Result<String> {
+    template.prs(sprs, num.unwrap_or(usize::MAX))
+}
+
+pub fn templated_reviews(reviews: &[Review], template: &Template) -> Result<String> {
+    template.reviews(reviews)
+}
+
+/// The HTML report with the pull requests to review and the follow-up reviews
+pub fn report(sprs: &[ScoredPr], output: &Output, reviews: &[Review], summary: &Summary) -> String {
+    html::report(
+        Some((sprs, output.limit())),
+        Some(reviews),
+        output.summary(summary),
+    )
+}
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    tool: &'static str,
+    tool_version: &'static str,
+    #[serde(flatten)]
+    summary: &'a Summary,
+    /// The pull requests excluded by the filters
+    filtered: usize,
+    shown: usize,
 }
 
-fn json_reviews(reviews: &[Review]) -> String {
-    match serde_json::to_string(&reviews) {
-        Ok(json) => json,
-        Err(error) => error.to_string(),
+impl<'a> Metadata<'a> {
+    fn new(summary: &'a Summary, shown: usize) -> Self {
+        Metadata {
+            tool: env!("CARGO_PKG_NAME"),
+            tool_version: env!("CARGO_PKG_VERSION"),
+            summary,
+            filtered: summary.fetched.saturating_sub(summary.total),
+            shown,
+        }
     }
 }
 
+#[derive(Serialize)]
+struct PrsEnvelope<'a> {
+    version: u32,
+    metadata: Metadata<'a>,
+    pull_requests: &'a [ScoredPr],
+}
+
+#[derive(Serialize)]
+struct GroupsEnvelope<'a> {
+    version: u32,
+    metadata: Metadata<'a>,
+    groups: &'a [Group],
+}
+
+#[derive(Serialize)]
+struct ReviewsEnvelope<'a> {
+    version: u32,
+    metadata: Metadata<'a>,
+    reviews: &'a [Review],
+}
+
+fn json_prs(sprs: &[ScoredPr], limit: usize, summary: &Summary) -> Result<String> {
+    let sprs = &sprs[..limit.min(sprs.len())];
+    Ok(serde_json::to_string(&PrsEnvelope {
+        version: JSON_VERSION,
+        metadata: Metadata::new(summary, sprs.len()),
+        pull_requests: sprs,
+    })?)
+}
+
+fn json_reviews(reviews: &[Review], summary: &Summary) -> Result<String> {
+    Ok(serde_json::to_string(&ReviewsEnvelope {
+        version: JSON_VERSION,
+        metadata: Metadata::new(summary, reviews.len()),
+        reviews,
+    })?)
+}
+
+/// One JSON document per line
+fn ndjson<'a, T, I>(items: I) -> Result<String>
+where
+    T: Serialize + 'a,
+    I: Iterator<Item = &'a T>,
+{
+    Ok(items
+        .map(ndjson_line)
+        .collect::<Result<Vec<String>>>()?
+        .join("\n"))
+}
+
+/// The NDJSON lines are printed while the pull requests are fetched, unless they need the ranking:
+/// the quotas, the round robin and the best `--num` ones
+pub fn streamed(options: &PrArgs) -> bool {
+    options.format() == Format::Ndjson
+        && options.template.is_none()
+        && options.num.is_none()
+        && !diversity::enabled(options)
+}
+
+pub fn ndjson_line<T: Serialize>(item: &T) -> Result<String> {
+    Ok(serde_json::to_string(item)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::types::*;
     use super::*;
 
     fn make_scored_pr(title: &str, url: &str) -> ScoredPr {
-        let pr = Pr {
+        test_scored_pr(Pr {
             title: title.to_string(),
             url: url.to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
-            num_approvals: 0,
-            num_reviewers: 0,
             additions: 0,
-            deletions: 0,
-            based_on_main_branch: false,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
-            requested: false,
-            codeowner: false,
+            ..test_pr()
+        })
+    }
+
+    fn output(format: Format, num: Option<usize>) -> Output {
+        Output {
+            format,
+            num,
+            debug: false,
+            header: true,
+            summary: false,
+            group_by: None,
+            style: table::Style::default(),
+        }
+    }
+
+    fn summary() -> Summary {
+        Summary {
+            queries: vec!["is:pr is:open".to_string()],
+            user: "alice".to_string(),
+            generated_at: chrono::Utc::now(),
+            fetched: 5,
+            total: 3,
+        }
+    }
+
+    #[test]
+    fn test_prs_json_envelope() {
+        let prs_data = vec![
+            make_scored_pr("Add feature", "https://example.com/1"),
+            make_scored_pr("Fix bug", "https://example.com/2"),
+        ];
+        let result = prs(&prs_data, &output(Format::Json, Some(1)), &summary()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(json["version"], 1);
+        let metadata = &json["metadata"];
+        assert_eq!(metadata["tool"], "ateam");
+        assert_eq!(metadata["tool_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(metadata["user"], "alice");
+        assert_eq!(metadata["queries"][0], "is:pr is:open");
+        assert!(metadata["generated_at"].is_string());
+        assert_eq!(metadata["fetched"], 5);
+        assert_eq!(metadata["filtered"], 2);
+        assert_eq!(metadata["total"], 3);
+        assert_eq!(metadata["shown"], 1);
+        assert_eq!(json["pull_requests"].as_array().unwrap().len(), 1);
+        assert_eq!(json["pull_requests"][0]["pr"]["title"], "Add feature");
+    }
+
+    #[test]
+    fn test_prs_grouped() {
+        let mut prs_data = vec![
+            make_scored_pr("Add feature", "https://example.com/1"),
+            make_scored_pr("Fix bug", "https://example.com/2"),
+            make_scored_pr("Update docs", "https://example.com/3"),
+        ];
+        prs_data[1].pr.repo = "frisoft/other".to_string();
+        let output = Output {
+            group_by: Some(GroupBy::Repo),
+            ..output(Format::Json, None)
+        };
+        let result = prs(&prs_data, &output, &summary()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(json["groups"][0]["name"], "frisoft/ateam");
+        assert_eq!(json["groups"][0]["count"], 2);
+        assert_eq!(
+            json["groups"][0]["pull_requests"][1]["pr"]["title"],
+            "Update docs"
+        );
+        assert_eq!(json["groups"][1]["name"], "frisoft/other");
+
+        let output = Output {
+            format: Format::Table,
+            num: Some(2),
+            ..output
+        };
+        let result = prs(&prs_data, &output, &summary()).unwrap();
+        assert!(result.starts_with("frisoft/ateam (1)\n"));
+        assert!(result.contains("\n\nfrisoft/other (1)\n"));
+        assert!(!result.contains("Update docs"));
+    }
+
+    #[test]
+    fn test_prs_ndjson_format() {
+        let prs_data = vec![
+            make_scored_pr("Add feature", "https://example.com/1"),
+            make_scored_pr("Fix bug", "https://example.com/2"),
+        ];
+        let result = prs(&prs_data, &output(Format::Ndjson, None), &summary()).unwrap();
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let json: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(json["pr"]["title"], "Fix bug");
+    }
+
+    #[test]
+    fn test_streamed() {
+        use clap::Parser;
+        let options = |args: &[&str]| {
+            let mut argv = vec!["ateam", "pr"];
+            argv.extend_from_slice(args);
+            match super::super::cli::Ateam::parse_from(argv).cmd {
+                super::super::cli::Command::Pr(options) => options,
+                super::super::cli::Command::Followup(_) | super::super::cli::Command::Tui(_) => {
+                    unreachable!()
+                }
+            }
         };
-        let score = Score::from_pr(1, &pr);
-        ScoredPr { pr, score }
+        assert!(streamed(&options(&["--format", "ndjson"])));
+        assert!(!streamed(&options(&[])));
+        assert!(!streamed(&options(&["--format", "json"])));
+        // The best ranked ones, not the first fetched ones
+        assert!(!streamed(&options(&["--format", "ndjson", "--num", "3"])));
+        assert!(!streamed(&options(&[
+            "--format",
+            "ndjson",
+            "--max-per-repo",
+            "2"
+        ])));
+        assert!(!streamed(&options(&[
+            "--format",
+            "ndjson",
+            "--template",
+            "{{ url }}"
+        ])));
     }
 
     #[test]
@@ -83,7 +378,7 @@ mod tests {
             make_scored_pr("Add feature", "https://example.com/1"),
             make_scored_pr("Fix bug", "https://example.com/2"),
         ];
-        let result = prs(&prs_data, None, false, false, true);
+        let result = prs(&prs_data, &output(Format::Json, None), &summary()).unwrap();
         assert!(result.contains("Add feature"));
         assert!(result.contains("Fix bug"));
     }
@@ -94,7 +389,7 @@ mod tests {
             make_scored_pr("Add feature", "https://example.com/1"),
             make_scored_pr("Fix bug", "https://example.com/2"),
         ];
-        let result = prs(&prs_data, None, false, true, false);
+        let result = prs(&prs_data, &output(Format::Short, None), &summary()).unwrap();
         assert!(result.contains("https://example.com/1"));
         assert!(result.contains("https://example.com/2"));
     }
@@ -106,7 +401,7 @@ mod tests {
             make_scored_pr("Fix bug", "https://example.com/2"),
             make_scored_pr("Update docs", "https://example.com/3"),
         ];
-        let result = prs(&prs_data, Some(2), false, true, false);
+        let result = prs(&prs_data, &output(Format::Short, Some(2)), &summary()).unwrap();
         assert!(result.contains("https://example.com/1"));
         assert!(result.contains("https://example.com/2"));
         assert!(!result.contains("https://example.com/3"));
@@ -115,7 +410,7 @@ mod tests {
     #[test]
     fn test_prs_empty() {
         let prs_data: Vec<ScoredPr> = vec![];
-        let result = prs(&prs_data, None, false, true, false);
+        let result = prs(&prs_data, &output(Format::Short, None), &summary()).unwrap();
         assert_eq!(result.len(), 0);
     }
 
@@ -125,7 +420,7 @@ mod tests {
             make_scored_pr("Add feature", "https://example.com/1"),
             make_scored_pr("Fix bug", "https://example.com/2"),
         ];
-        let result = prs(&prs_data, Some(10), false, true, false);
+        let result = prs(&prs_data, &output(Format::Short, Some(10)), &summary()).unwrap();
         assert!(result.contains("https://example.com/1"));
         assert!(result.contains("https://example.com/2"));
     }
@@ -137,7 +432,7 @@ mod tests {
             make_scored_pr("Fix bug", "https://example.com/2"),
             make_scored_pr("Update docs", "https://example.com/3"),
         ];
-        let result = prs(&prs_data, Some(2), false, true, false);
+        let result = prs(&prs_data, &output(Format::Short, Some(2)), &summary()).unwrap();
         let count = result.matches("https://example.com/").count();
         assert_eq!(count, 2);
     }
@@ -145,10 +440,46 @@ mod tests {
     #[test]
     fn test_prs_default_format() {
         let prs_data = vec![make_scored_pr("Add feature", "https://example.com/1")];
-        let result = prs(&prs_data, None, false, false, false);
+        let result = prs(&prs_data, &output(Format::Table, None), &summary()).unwrap();
         assert!(result.contains("Add feature"));
     }
 
+    #[test]
+    fn test_prs_markdown_format() {
+        let prs_data = vec![make_scored_pr("Add feature", "https://example.com/1")];
+        let result = prs(&prs_data, &output(Format::Markdown, None), &summary()).unwrap();
+        assert!(result.contains("| [Add feature](https://example.com/1) |"));
+    }
+
+    #[test]
+    fn test_prs_csv_format() {
+        let prs_data = vec![make_scored_pr("Add feature", "https://example.com/1")];
+        let result = prs(
+            &prs_data,
+            &Output {
+                header: false,
+                ..output(Format::Csv, None)
+            },
+            &summary(),
+        )
+        .unwrap();
+        assert!(result.starts_with("https://example.com/1,Add feature,"));
+    }
+
+    #[test]
+    fn test_prs_short_hyperlinks() {
+        let prs_data = vec![make_scored_pr("Add feature", "https://example.com/1")];
+        let output = Output {
+            style: table::Style {
+                hyperlinks: true,
+                ..table::Style::default()
+            },
+            ..output(Format::Short, None)
+        };
+        let result = prs(&prs_data, &output, &summary()).unwrap();
+        assert!(result.starts_with("\x1b]8;;https://example.com/1\x1b\\Add feature\x1b]8;;\x1b\\"));
+    }
+
     // Tests for render::reviews function
     fn make_review(state: ReviewState, url: &str, title: &str) -> Review {
         Review {
@@ -168,7 +499,7 @@ mod tests {
                 "Add feature",
             ),
         ];
-        let result = reviews(&reviews_data, true);
+        let result = reviews(&reviews_data, &output(Format::Json, None), &summary()).unwrap();
         assert!(result.contains("Dismissed"));
         assert!(result.contains("Add feature"));
     }
@@ -180,17 +511,16 @@ mod tests {
             "https://example.com/1",
             "Fix bug",
         )];
-        let result = reviews(&reviews_data, false);
+        let result = reviews(&reviews_data, &output(Format::Table, None), &summary()).unwrap();
         assert!(result.contains("Fix bug"));
     }
 
     #[test]
     fn test_reviews_empty() {
         let reviews_data: Vec<Review> = vec![];
-        let result = reviews(&reviews_data, true);
-        assert!(result.starts_with('['));
-        assert!(result.ends_with(']'));
-        assert_eq!(result, "[]");
+        let result = reviews(&reviews_data, &output(Format::Json, None), &summary()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(json["reviews"], serde_json::json!([]));
     }
 
     #[test]
@@ -200,7 +530,7 @@ mod tests {
             "https://example.com/pr/123",
             "Update code",
         )];
-        let result = reviews(&reviews_data, false);
+        let result = reviews(&reviews_data, &output(Format::Table, None), &summary()).unwrap();
         assert!(result.contains("https://example.com/pr/123"));
         assert!(result.contains("Update code"));
     }
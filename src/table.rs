@@ -1,105 +1,267 @@
-use super::types::{Files, Review, ScoredPr, TestsState};
+use super::cli::{Column, ColumnSpec, DEFAULT_COLUMNS};
+use super::color::Colors;
+use super::hyperlink;
+use super::types::{MergeState, Review, ScoredPr, TestsState};
 use comfy_table::modifiers::UTF8_ROUND_CORNERS;
 use comfy_table::presets::UTF8_FULL;
-use comfy_table::{ColumnConstraint, ContentArrangement, Table};
+use comfy_table::{ColumnConstraint, ContentArrangement, Table, Width as ColumnWidth};
 use terminal_size::{Height, Width, terminal_size};
 
 #[cfg(test)]
-use super::types::{Labels, Pr, ReviewState, Score};
+use super::types::{Pr, ReviewState, test_pr, test_scored_pr};
 
-pub fn from(sprs: &[ScoredPr], limit: usize, debug: bool) -> Table {
+/// How the pull requests are shown in the table
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub columns: Vec<ColumnSpec>,
+    pub colors: Option<Colors>,
+    /// The titles link to the pull requests, instead of showing the URLs
+    pub hyperlinks: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            columns: DEFAULT_COLUMNS
+                .iter()
+                .map(|name| name.parse().expect("a valid column"))
+                .collect(),
+            colors: None,
+            hyperlinks: false,
+        }
+    }
+}
+
+/// The score factors and the changed files, added with --debug after the chosen columns
+const DEBUG_COLUMNS: [Column; 13] = [
+    Column::ScoreAge,
+    Column::ScoreCi,
+    Column::ScoreConversations,
+    Column::ScoreApprovals,
+    Column::ScoreReviewers,
+    Column::ScoreAdditions,
+    Column::ScoreDeletions,
+    Column::ScoreOnMain,
+    Column::ScoreMerge,
+    Column::ScoreBlame,
+    Column::ScoreRequested,
+    Column::ScoreCodeowner,
+    Column::Files,
+];
+
+pub fn from(sprs: &[ScoredPr], limit: usize, debug: bool, style: &Style) -> Table {
+    let mut columns: Vec<ColumnSpec> = style
+        .columns
+        .iter()
+        .filter(|spec| !(style.hyperlinks && spec.column == Column::Url))
+        .copied()
+        .collect();
+    if debug {
+        for column in DEBUG_COLUMNS {
+            if !columns.iter().any(|spec| spec.column == column) {
+                columns.push(ColumnSpec {
+                    column,
+                    width: None,
+                });
+            }
+        }
+    }
     let mut table = build_table();
-    table.set_header(vec![
-        "Pull request",
-        "Age",
-        "CI",
-        "O.C.",
-        "Appr.",
-        "Diff",
-        "On Main",
-        "Blame",
-        "Req.",
-        "C. Owner",
-        "Score",
-    ]);
-
-    for c in 1..8 {
-        table
-            .column_mut(c)
-            .expect("a column")
-            .set_constraint(ColumnConstraint::ContentWidth);
+    table.set_header(columns.iter().map(|spec| header(spec.column)));
+
+    for (i, spec) in columns.iter().enumerate() {
+        let constraint = match spec.width {
+            Some(width) => Some(ColumnConstraint::UpperBoundary(ColumnWidth::Fixed(width))),
+            None if wraps(spec.column) => None,
+            None => Some(ColumnConstraint::ContentWidth),
+        };
+        if let Some(constraint) = constraint {
+            table
+                .column_mut(i)
+                .expect("a column")
+                .set_constraint(constraint);
+        }
     }
 
-    for spr in sprs.iter().take(limit) {
-        table.add_row(pr_row(spr, debug));
+    let count = sprs.len().min(limit);
+    for (rank, spr) in sprs.iter().take(limit).enumerate() {
+        table.add_row(columns.iter().map(|spec| {
+            let text = cell(spr, spec.column, style);
+            match style.colors {
+                Some(colors) => colored(colors, spr, spec.column, &text, rank, count),
+                None => text,
+            }
+        }));
     }
 
     table
 }
 
-fn pr_row(spr: &ScoredPr, debug: bool) -> Vec<String> {
-    let debug_info = if debug {
-        format!(
-            "\nAge:{:.1} T:{:.1} OC:{:.1} Ap:{:.1} R:{:.1} +:{:.1} -:{:.1} M.br:{:.1} Bl:{:.1} Req.:{:.1} C.Owner:{:.1} Tot:{:.1}{}\n",
-            spr.score.age,
-            spr.score.tests_result,
-            spr.score.open_conversations,
-            spr.score.num_approvals,
-            spr.score.num_reviewers,
-            spr.score.additions,
-            spr.score.deletions,
-            spr.score.based_on_main_branch,
-            spr.score.blame,
-            spr.score.requested,
-            spr.score.codeowner,
-            spr.score.total(),
-            show_files(&spr.pr.files)
-        )
+fn header(column: Column) -> &'static str {
+    match column {
+        Column::Pr => "Pull request",
+        Column::Title => "Title",
+        Column::Url => "URL",
+        Column::Repo => "Repo",
+        Column::Author => "Author",
+        Column::Labels => "Labels",
+        Column::Age => "Age",
+        Column::Ci => "CI",
+        Column::AllCi => "All CI",
+        Column::Conversations => "O.C.",
+        Column::Approvals => "Appr.",
+        Column::Diff => "Diff",
+        Column::ChangedFiles => "Files",
+        Column::Files => "Changed files",
+        Column::OnMain => "On Main",
+        Column::Merge => "Merge",
+        Column::Blame => "Blame",
+        Column::Requested => "Req.",
+        Column::Codeowner => "C. Owner",
+        Column::Team => "Team",
+        Column::Score => "Score",
+        Column::ScoreAge => "S. Age",
+        Column::ScoreCi => "S. CI",
+        Column::ScoreConversations => "S. O.C.",
+        Column::ScoreApprovals => "S. Appr.",
+        Column::ScoreReviewers => "S. Rev.",
+        Column::ScoreAdditions => "S. +",
+        Column::ScoreDeletions => "S. -",
+        Column::ScoreOnMain => "S. On Main",
+        Column::ScoreMerge => "S. Merge",
+        Column::ScoreBlame => "S. Blame",
+        Column::ScoreRequested => "S. Req.",
+        Column::ScoreCodeowner => "S. C. Owner",
+    }
+}
+
+/// The columns with long text, that wrap to the available width
+fn wraps(column: Column) -> bool {
+    matches!(
+        column,
+        Column::Pr | Column::Title | Column::Labels | Column::Files
+    )
+}
+
+fn cell(spr: &ScoredPr, column: Column, style: &Style) -> String {
+    let pr = &spr.pr;
+    let score = &spr.score;
+    match column {
+        Column::Pr => pr_cell(spr, style),
+        Column::Title if style.hyperlinks => hyperlink::link(&pr.url, &pr.title),
+        Column::Title => pr.title.clone(),
+        Column::Url => pr.url.clone(),
+        Column::Repo => pr.repo.clone(),
+        Column::Author => pr.author.clone(),
+        Column::Labels => match style.colors {
+            Some(colors) => colors.labels(&pr.labels),
+            None => pr.labels.to_string(),
+        },
+        Column::Age => show_duration(pr.last_commit_age_min),
+        Column::Ci => tests_result_label(&pr.tests_result).to_string(),
+        Column::AllCi => tests_result_label(&pr.all_tests_result).to_string(),
+        Column::Conversations => pr.open_conversations.to_string(),
+        Column::Approvals => format!("{}/{}", pr.num_approvals, pr.num_reviewers),
+        Column::Diff => format!("+{} -{}", pr.additions, pr.deletions),
+        Column::ChangedFiles => pr.changed_files.to_string(),
+        Column::Files => pr.files.0.join("\n"),
+        Column::OnMain => show_bool(pr.based_on_main_branch).to_string(),
+        Column::Merge => merge_state_label(&pr.merge_state).to_string(),
+        Column::Blame => show_bool(pr.blame).to_string(),
+        Column::Requested => show_bool(pr.requested).to_string(),
+        Column::Codeowner => show_bool(pr.codeowner).to_string(),
+        Column::Team => pr.team.clone().unwrap_or_else(|| "-".to_string()),
+        Column::Score => show_score(score.total()),
+        Column::ScoreAge => show_score(score.age),
+        Column::ScoreCi => show_score(score.tests_result),
+        Column::ScoreConversations => show_score(score.open_conversations),
+        Column::ScoreApprovals => show_score(score.num_approvals),
+        Column::ScoreReviewers => show_score(score.num_reviewers),
+        Column::ScoreAdditions => show_score(score.additions),
+        Column::ScoreDeletions => show_score(score.deletions),
+        Column::ScoreOnMain => show_score(score.based_on_main_branch),
+        Column::ScoreMerge => show_score(score.merge_state),
+        Column::ScoreBlame => show_score(score.blame),
+        Column::ScoreRequested => show_score(score.requested),
+        Column::ScoreCodeowner => show_score(score.codeowner),
+    }
+}
+
+/// The heat colors of the CI, the age and the score
+fn colored(
+    colors: Colors,
+    spr: &ScoredPr,
+    column: Column,
+    text: &str,
+    rank: usize,
+    count: usize,
+) -> String {
+    match column {
+        Column::Ci => colors.ci(&spr.pr.tests_result, text),
+        Column::AllCi => colors.ci(&spr.pr.all_tests_result, text),
+        Column::Age => colors.age(spr.pr.last_commit_age_min, text),
+        Column::Score => colors.score(rank, count, text),
+        _ => text.to_string(),
+    }
+}
+
+fn pr_cell(spr: &ScoredPr, style: &Style) -> String {
+    let labels = match style.colors {
+        Some(colors) => colors.labels(&spr.pr.labels),
+        None => spr.pr.labels.to_string(),
+    };
+    let title = if style.hyperlinks {
+        hyperlink::link(&spr.pr.url, &spr.pr.title)
     } else {
-        String::new()
+        format!("{}\n{}", spr.pr.url, spr.pr.title)
     };
-    vec![
-        format!(
-            "{}\n{} {}{}",
-            spr.pr.url, spr.pr.title, spr.pr.labels, debug_info
-        ),
-        show_duration(spr.pr.last_commit_age_min),
-        tests_result_label(&spr.pr.tests_result).to_string(),
-        spr.pr.open_conversations.to_string(),
-        format!("{}/{}", spr.pr.num_approvals, spr.pr.num_reviewers),
-        format!("+{} -{}", spr.pr.additions, spr.pr.deletions),
-        show_bool(spr.pr.based_on_main_branch).to_string(),
-        show_bool(spr.pr.blame).to_string(),
-        show_bool(spr.pr.requested).to_string(),
-        show_bool(spr.pr.codeowner).to_string(),
-        format!("{:.1}", spr.score.total()),
-    ]
+    format!(
+        "{title} {labels}{}",
+        show_failed_checks(&spr.pr.failed_checks),
+    )
+}
+
+fn show_score(score: f64) -> String {
+    format!("{score:.1}")
 }
 
 const YES: &str = "yes";
 const NO: &str = "no";
-fn show_bool(value: bool) -> &'static str {
+pub fn show_bool(value: bool) -> &'static str {
     if value { YES } else { NO }
 }
 
-fn tests_result_label(tests_result: &TestsState) -> &'static str {
+pub fn tests_result_label(tests_result: &TestsState) -> &'static str {
     match tests_result {
         TestsState::Success => "OK",
         TestsState::Pending => "..",
         TestsState::Failure => "Fail",
+        TestsState::Missing => "Miss",
         TestsState::None => "-",
     }
 }
 
-fn show_files(files: &Files) -> String {
-    if files.0.is_empty() {
+pub fn merge_state_label(merge_state: &MergeState) -> &'static str {
+    match merge_state {
+        MergeState::Clean => "clean",
+        MergeState::Behind => "behind",
+        MergeState::Blocked => "blocked",
+        MergeState::Unstable => "unstable",
+        MergeState::HasHooks => "hooks",
+        MergeState::Conflicting => "conflict",
+        MergeState::Unknown => "?",
+    }
+}
+
+fn show_failed_checks(failed_checks: &[String]) -> String {
+    if failed_checks.is_empty() {
         String::new()
     } else {
-        format!("\n{}\n", files.0.join("\n"))
+        format!("\nFailed: {}", failed_checks.join(", "))
     }
 }
 
-fn show_duration(minutes: Option<i64>) -> String {
+pub fn show_duration(minutes: Option<i64>) -> String {
     match minutes {
         Some(min) => {
             let d = min / 60 / 24;
@@ -143,23 +305,34 @@ fn build_table() -> Table {
     table
 }
 
-pub fn from_reviews(reviews: &[Review]) -> Table {
+pub fn from_reviews(reviews: &[Review], hyperlinks: bool) -> Table {
     let mut table = build_table();
-    table.set_header(vec!["Review", "State", "Pull request"]);
+    if hyperlinks {
+        table.set_header(vec!["State", "Pull request"]);
+    } else {
+        table.set_header(vec!["Review", "State", "Pull request"]);
+    }
 
     for review in reviews {
-        table.add_row(review_row(review));
+        table.add_row(review_row(review, hyperlinks));
     }
 
     table
 }
 
-fn review_row(review: &Review) -> Vec<String> {
-    vec![
-        review.url.clone(),
-        review.state.to_string(),
-        review.pr_title.clone(),
-    ]
+fn review_row(review: &Review, hyperlinks: bool) -> Vec<String> {
+    if hyperlinks {
+        vec![
+            review.state.to_string(),
+            hyperlink::link(&review.url, &review.pr_title),
+        ]
+    } else {
+        vec![
+            review.url.clone(),
+            review.state.to_string(),
+            review.pr_title.clone(),
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -180,32 +353,26 @@ mod tests {
         requested: bool,
         codeowner: bool,
     ) -> ScoredPr {
-        let pr = Pr {
+        test_scored_pr(Pr {
             title: title.to_string(),
             url: url.to_string(),
-            last_commit_pushed_date: None,
             last_commit_age_min: age_min,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
             num_approvals: approvals,
             num_reviewers: reviewers,
             additions,
             deletions,
             based_on_main_branch: on_main,
-            files: Files(vec![]),
             blame,
-            labels: Labels(vec![]),
             requested,
             codeowner,
-        };
-        let score = Score::from_pr(1, &pr);
-        ScoredPr { pr, score }
+            ..test_pr()
+        })
     }
 
     #[test]
     fn test_table_from_empty() {
         let prs: &[ScoredPr] = &[];
-        let result = from(prs, 10, false);
+        let result = from(prs, 10, false, &Style::default());
         assert_eq!(result.row_count(), 0);
     }
 
@@ -224,7 +391,7 @@ mod tests {
             false,
             false,
         )];
-        let result = from(&prs, 10, false);
+        let result = from(&prs, 10, false, &Style::default());
         assert_eq!(result.row_count(), 1);
     }
 
@@ -258,7 +425,7 @@ mod tests {
                 false,
             ),
         ];
-        let result = from(&prs, 10, false);
+        let result = from(&prs, 10, false, &Style::default());
         assert_eq!(result.row_count(), 2);
     }
 
@@ -305,7 +472,7 @@ mod tests {
                 false,
             ),
         ];
-        let result = from(&prs, 2, false);
+        let result = from(&prs, 2, false, &Style::default());
         assert_eq!(result.row_count(), 2);
     }
 
@@ -324,7 +491,7 @@ mod tests {
             false,
             false,
         )];
-        let result = from(&prs, 10, false);
+        let result = from(&prs, 10, false, &Style::default());
         assert_eq!(result.row_count(), 1);
     }
 
@@ -343,8 +510,111 @@ mod tests {
             false,
             false,
         )];
-        let result = from(&prs, 10, true);
+        let style = Style {
+            columns: vec!["pr".parse().unwrap(), "score-age".parse().unwrap()],
+            colors: None,
+            hyperlinks: false,
+        };
+        let result = from(&prs, 10, true, &style);
         assert_eq!(result.row_count(), 1);
+        // The chosen columns, then the other score factors and the changed files
+        assert_eq!(result.header().unwrap().cell_count(), 2 + 12);
+        let text = result.to_string();
+        assert!(text.contains("S. Age"));
+        assert!(text.contains("S. C. Owner"));
+        assert!(text.contains("Changed files"));
+        assert!(!text.contains("Tot:"));
+    }
+
+    #[test]
+    fn test_table_from_columns() {
+        let prs = vec![make_scored_pr(
+            "Fix bug",
+            "https://example.com/1",
+            Some(60),
+            2,
+            1,
+            100,
+            50,
+            true,
+            false,
+            false,
+            false,
+        )];
+        let style = Style {
+            columns: vec![
+                "author".parse().unwrap(),
+                "title:20".parse().unwrap(),
+                "score-requested".parse().unwrap(),
+            ],
+            colors: None,
+            hyperlinks: false,
+        };
+        let result = from(&prs, 10, false, &style);
+        assert_eq!(result.header().unwrap().cell_count(), 3);
+        let text = result.to_string();
+        assert!(text.contains("Author"));
+        assert!(text.contains("alice"));
+        assert!(text.contains("S. Req."));
+        assert!(!text.contains("https://example.com/1"));
+        assert_eq!(
+            result.column(1).unwrap().constraint(),
+            Some(&ColumnConstraint::UpperBoundary(ColumnWidth::Fixed(20)))
+        );
+    }
+
+    #[test]
+    fn test_table_from_all_ci() {
+        let mut prs = vec![make_scored_pr(
+            "Fix bug",
+            "https://example.com/1",
+            Some(60),
+            2,
+            1,
+            100,
+            50,
+            true,
+            false,
+            false,
+            false,
+        )];
+        prs[0].pr.all_tests_result = TestsState::Failure;
+        let style = Style {
+            columns: vec!["ci".parse().unwrap(), "all-ci".parse().unwrap()],
+            colors: None,
+            hyperlinks: false,
+        };
+        let text = from(&prs, 10, false, &style).to_string();
+        assert!(text.contains("All CI"));
+        assert!(text.contains("OK"));
+        assert!(text.contains("Fail"));
+    }
+
+    #[test]
+    fn test_table_from_hyperlinks() {
+        let prs = vec![make_scored_pr(
+            "Fix bug",
+            "https://example.com/1",
+            Some(60),
+            2,
+            1,
+            100,
+            50,
+            true,
+            false,
+            false,
+            false,
+        )];
+        let style = Style {
+            columns: vec!["pr".parse().unwrap(), "url".parse().unwrap()],
+            colors: None,
+            hyperlinks: true,
+        };
+        let result = from(&prs, 10, false, &style);
+        assert_eq!(result.header().unwrap().cell_count(), 1);
+        let text = result.to_string();
+        assert!(text.contains("\x1b]8;;https://example.com/1\x1b\\Fix bug\x1b]8;;\x1b\\"));
+        assert_eq!(text.matches("https://example.com/1").count(), 1);
     }
 
     // Tests for from_reviews
@@ -359,7 +629,7 @@ mod tests {
     #[test]
     fn test_table_from_reviews_empty() {
         let reviews: &[Review] = &[];
-        let result = from_reviews(reviews);
+        let result = from_reviews(reviews, false);
         assert_eq!(result.row_count(), 0);
     }
 
@@ -370,7 +640,7 @@ mod tests {
             "https://example.com/1",
             "Fix bug",
         )];
-        let result = from_reviews(&reviews);
+        let result = from_reviews(&reviews, false);
         assert_eq!(result.row_count(), 1);
     }
 
@@ -384,7 +654,22 @@ mod tests {
                 "Add feature",
             ),
         ];
-        let result = from_reviews(&reviews);
+        let result = from_reviews(&reviews, false);
         assert_eq!(result.row_count(), 2);
     }
+
+    #[test]
+    fn test_table_from_reviews_hyperlinks() {
+        let reviews = vec![make_review(
+            ReviewState::Dismissed,
+            "https://example.com/1",
+            "Fix bug",
+        )];
+        let result = from_reviews(&reviews, true);
+        assert_eq!(result.header().unwrap().cell_count(), 2);
+        assert_eq!(
+            result.to_string().matches("https://example.com/1").count(),
+            1
+        );
+    }
 }
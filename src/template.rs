@@ -0,0 +1,223 @@
+use super::types::{Review, ScoredPr};
+use anyhow::Result;
+use minijinja::{Environment, UndefinedBehavior, Value, context};
+use std::str::FromStr;
+
+/// A user-defined output format, used by --template. The template is rendered once per pull request
+/// (or review) with [minijinja](https://docs.rs/minijinja) and the results are joined by new lines:
+///
+/// `{{ rank }}. {{ title }} ({{ author }}){% for label in labels %} #{{ label.name }}{% endfor %}`
+///
+/// A value starting with `@` is the path of a file with the template. Its final new line is dropped.
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = match s.strip_prefix('@') {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|error| format!("Can't read the template file {path}: {error}"))?,
+            None => s.to_string(),
+        };
+        environment()
+            .template_from_str(&source)
+            .map_err(|error| error.to_string())?;
+        Ok(Template { source })
+    }
+}
+
+impl Template {
+    /// Every field of the pull request, `score` with the score factors, `total` and the `rank`, from 1
+    pub(crate) fn prs(&self, sprs: &[ScoredPr], limit: usize) -> Result<String> {
+        self.render(sprs.iter().take(limit).enumerate().map(|(i, spr)| {
+            context! {
+                rank => i + 1,
+                total => spr.score.total(),
+                score => Value::from_serialize(&spr.score),
+                ..Value::from_serialize(&spr.pr)
+            }
+        }))
+    }
+
+    /// `url`, `state`, `pr_title` and the `rank`, from 1
+    pub(crate) fn reviews(&self, reviews: &[Review]) -> Result<String> {
+        self.render(reviews.iter().enumerate().map(|(i, review)| {
+            context! {
+                rank => i + 1,
+                url => review.url,
+                state => review.state.to_string(),
+                pr_title => review.pr_title,
+            }
+        }))
+    }
+
+    fn render<I>(&self, contexts: I) -> Result<String>
+    where
+        I: Iterator<Item = Value>,
+    {
+        let environment = environment();
+        let template = environment.template_from_str(&self.source)?;
+        let lines = contexts
+            .map(|context| template.render(context))
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(lines.join("\n"))
+    }
+}
+
+fn environment() -> Environment<'static> {
+    let mut environment = Environment::new();
+    // A misspelled field is an error, not an empty string
+    environment.set_undefined_behavior(UndefinedBehavior::Strict);
+    environment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::*;
+    use super::*;
+
+    fn make_scored_pr(title: &str, labels: &[&str]) -> ScoredPr {
+        test_scored_pr(Pr {
+            title: title.to_string(),
+            last_commit_age_min: Some(90),
+            tests_result: TestsState::Failure,
+            all_tests_result: TestsState::Failure,
+            open_conversations: 1,
+            num_reviewers: 1,
+            additions: 10,
+            deletions: 2,
+            changed_files: 2,
+            based_on_main_branch: true,
+            merge_state: MergeState::Behind,
+            files: Files(vec!["src/lib.rs".to_string(), "README.md".to_string()]),
+            labels: Labels(
+                labels
+                    .iter()
+                    .map(|name| Label {
+                        name: (*name).to_string(),
+                        color: "ededed".to_string(),
+                    })
+                    .collect(),
+            ),
+            requested: true,
+            ..test_pr()
+        })
+    }
+
+    fn template(source: &str) -> Template {
+        source.parse().unwrap()
+    }
+
+    #[test]
+    fn test_fields_and_rank() {
+        let prs = vec![make_scored_pr("One", &[]), make_scored_pr("Two", &[])];
+        let result = template("{{ rank }}. {{ title }} by {{ author }} in {{ repo }} +{{ additions }} {{ tests_result }} {{ merge_state }}")
+            .prs(&prs, 10)
+            .unwrap();
+        assert_eq!(
+            result,
+            "1. One by alice in frisoft/ateam +10 Failure Behind\n2. Two by alice in frisoft/ateam +10 Failure Behind"
+        );
+    }
+
+    #[test]
+    fn test_score() {
+        let prs = vec![make_scored_pr("One", &[])];
+        let result = template("{{ score.requested }} {{ score.age }} {{ total|round(1) }}")
+            .prs(&prs, 10)
+            .unwrap();
+        let spr = &prs[0];
+        assert_eq!(
+            result,
+            format!(
+                "{:.1} {:.1} {:.1}",
+                spr.score.requested,
+                spr.score.age,
+                spr.score.total()
+            )
+        );
+    }
+
+    #[test]
+    fn test_conditionals_and_loops() {
+        let prs = vec![
+            make_scored_pr("One", &["bug", "ui"]),
+            make_scored_pr("Two", &[]),
+        ];
+        let result = template(
+            "{{ title }}{% if labels %} [{% for l in labels %}{{ l.name }}{% if not loop.last %},{% endif %}{% endfor %}]{% endif %}{% if team %} @{{ team }}{% endif %}{% for f in files %} {{ f }}{% endfor %}",
+        )
+        .prs(&prs, 1)
+        .unwrap();
+        assert_eq!(result, "One [bug,ui] src/lib.rs README.md");
+    }
+
+    #[test]
+    fn test_undefined_is_an_error() {
+        let prs = vec![make_scored_pr("One", &[])];
+        for source in [
+            "{{ titel }}",
+            "{{ score.speed }}",
+            "{% if reviewd %}x{% endif %}",
+        ] {
+            let error = template(source).prs(&prs, 10).unwrap_err();
+            let error = error.downcast_ref::<minijinja::Error>().unwrap();
+            assert_eq!(
+                error.kind(),
+                minijinja::ErrorKind::UndefinedError,
+                "{source}"
+            );
+        }
+
+        // The reviews have pr_title, not the title of the pull requests
+        let reviews = vec![Review {
+            state: ReviewState::Dismissed,
+            url: "https://example.com/1".to_string(),
+            pr_title: "Fix bug".to_string(),
+        }];
+        let error = template("{{ title }}").reviews(&reviews).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<minijinja::Error>().unwrap().kind(),
+            minijinja::ErrorKind::UndefinedError
+        );
+        assert!(template("{{ pr_title }}").reviews(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        assert!("{% if %}".parse::<Template>().is_err());
+        assert!("@/does/not/exist".parse::<Template>().is_err());
+    }
+
+    #[test]
+    fn test_file() {
+        let path = std::env::temp_dir().join(format!("ateam-template-{}.txt", std::process::id()));
+        std::fs::write(&path, "{{ url }}\n").unwrap();
+        let template: Template = format!("@{}", path.display()).parse().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let prs = vec![make_scored_pr("One", &[]), make_scored_pr("Two", &[])];
+        assert_eq!(
+            template.prs(&prs, 10).unwrap(),
+            "https://github.com/frisoft/ateam/pull/1\nhttps://github.com/frisoft/ateam/pull/1"
+        );
+    }
+
+    #[test]
+    fn test_reviews() {
+        let reviews = vec![Review {
+            state: ReviewState::Dismissed,
+            url: "https://example.com/1".to_string(),
+            pr_title: "Fix bug".to_string(),
+        }];
+        assert_eq!(
+            template("{{ rank }} {{ state }}: {{ pr_title }} {{ url }}")
+                .reviews(&reviews)
+                .unwrap(),
+            "1 Dismissed: Fix bug https://example.com/1"
+        );
+    }
+}
@@ -0,0 +1,640 @@
+use super::cli::PrArgs;
+use super::client::{fetch_scored_prs, followup, sorted_ranked_prs};
+use super::diversity;
+use super::table::{merge_state_label, show_bool, show_duration, tests_result_label};
+use super::types::{Label, Review, ScoredPr, TestsState};
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How long to wait for a key before checking the refresh again
+const POLL: Duration = Duration::from_millis(100);
+
+const KEYS: &str = "↑↓ move  o open  r refresh  s snooze  S unsnooze  1 requested  2 code owner  3 hide failing CI  tab view  q quit";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum View {
+    #[default]
+    Prs,
+    Followup,
+}
+
+/// Quick filters of the pull requests already fetched
+#[derive(Debug, Default)]
+struct Toggles {
+    requested: bool,
+    codeowner: bool,
+    hide_failing: bool,
+}
+
+impl Toggles {
+    fn keep(&self, spr: &ScoredPr) -> bool {
+        (!self.requested || spr.pr.requested)
+            && (!self.codeowner || spr.pr.codeowner)
+            && !(self.hide_failing && matches!(spr.pr.tests_result, TestsState::Failure))
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        [
+            (self.requested, "requested"),
+            (self.codeowner, "code owner"),
+            (self.hide_failing, "no failing CI"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    Quit,
+    Open(String),
+    Refresh,
+}
+
+#[derive(Debug, Default)]
+struct App {
+    view: View,
+    prs: Vec<ScoredPr>,
+    reviews: Vec<Review>,
+    /// The URLs hidden until the end of the session
+    snoozed: HashSet<String>,
+    toggles: Toggles,
+    prs_state: ListState,
+    reviews_state: ListState,
+    status: String,
+}
+
+impl App {
+    /// The pull requests to show, with their rank in the full list, from 1
+    fn visible_prs(&self) -> Vec<(usize, &ScoredPr)> {
+        self.prs
+            .iter()
+            .enumerate()
+            .filter(|(_, spr)| !self.snoozed.contains(&spr.pr.url) && self.toggles.keep(spr))
+            .map(|(i, spr)| (i + 1, spr))
+            .collect()
+    }
+
+    fn visible_reviews(&self) -> Vec<&Review> {
+        self.reviews
+            .iter()
+            .filter(|review| !self.snoozed.contains(&review.url))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        match self.view {
+            View::Prs => self.visible_prs().len(),
+            View::Followup => self.visible_reviews().len(),
+        }
+    }
+
+    fn state(&mut self) -> &mut ListState {
+        match self.view {
+            View::Prs => &mut self.prs_state,
+            View::Followup => &mut self.reviews_state,
+        }
+    }
+
+    fn selected_url(&self) -> Option<String> {
+        match self.view {
+            View::Prs => self
+                .prs_state
+                .selected()
+                .and_then(|i| self.visible_prs().get(i).map(|(_, spr)| spr.pr.url.clone())),
+            View::Followup => self.reviews_state.selected().and_then(|i| {
+                self.visible_reviews()
+                    .get(i)
+                    .map(|review| review.url.clone())
+            }),
+        }
+    }
+
+    /// Keep the selection inside the list after it changes
+    fn clamp(&mut self) {
+        for view in [View::Prs, View::Followup] {
+            let current = self.view;
+            self.view = view;
+            let len = self.len();
+            let state = self.state();
+            let selected = match len {
+                0 => None,
+                len => Some(state.selected().unwrap_or(0).min(len - 1)),
+            };
+            state.select(selected);
+            self.view = current;
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.len().saturating_sub(1);
+        let state = self.state();
+        if let Some(selected) = state.selected() {
+            state.select(Some(selected.saturating_add_signed(delta).min(last)));
+        }
+    }
+
+    fn load(&mut self, fetched: Result<(Vec<ScoredPr>, Vec<Review>)>) {
+        match fetched {
+            Ok((prs, reviews)) => {
+                self.status = format!(
+                    "{} pull requests and {} reviews, refreshed at {}",
+                    prs.len(),
+                    reviews.len(),
+                    chrono::Local::now().format("%H:%M")
+                );
+                self.prs = prs;
+                self.reviews = reviews;
+            }
+            // The previous pull requests are kept
+            Err(error) => self.status = format!("Error: {error:#}"),
+        }
+        self.clamp();
+    }
+
+    fn handle(&mut self, code: KeyCode) -> Action {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('r') => return Action::Refresh,
+            KeyCode::Char('o') | KeyCode::Enter => {
+                return self.selected_url().map_or(Action::None, Action::Open);
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(10),
+            KeyCode::PageUp => self.move_by(-10),
+            KeyCode::Home | KeyCode::Char('g') => self.state().select_first(),
+            KeyCode::End | KeyCode::Char('G') => {
+                let last = self.len().checked_sub(1);
+                self.state().select(last);
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.view = match self.view {
+                    View::Prs => View::Followup,
+                    View::Followup => View::Prs,
+                };
+            }
+            KeyCode::Char('s') => {
+                if let Some(url) = self.selected_url() {
+                    self.status = format!("Snoozed {url} until the end of the session");
+                    self.snoozed.insert(url);
+                }
+            }
+            KeyCode::Char('S') => {
+                self.status = format!("{} unsnoozed", self.snoozed.len());
+                self.snoozed.clear();
+            }
+            KeyCode::Char('1') => self.toggles.requested = !self.toggles.requested,
+            KeyCode::Char('2') => self.toggles.codeowner = !self.toggles.codeowner,
+            KeyCode::Char('3') => self.toggles.hide_failing = !self.toggles.hide_failing,
+            _ => {}
+        }
+        self.clamp();
+        Action::None
+    }
+}
+
+/// The interactive view of `ateam tui`, until q is pressed
+pub async fn run(options: &PrArgs, github_api_token: &str, username: &str) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, options, github_api_token, username).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    options: &PrArgs,
+    github_api_token: &str,
+    username: &str,
+) -> Result<()> {
+    let mut app = App::default();
+    // The refresh runs in the background, so the keys work while it fetches
+    let mut refreshing = Some(refresh(options, github_api_token, username));
+    app.status = "Refreshing...".to_string();
+    loop {
+        if refreshing.as_ref().is_some_and(JoinHandle::is_finished) {
+            // A panic in the refresh is shown like a failed one
+            app.load(
+                refreshing
+                    .take()
+                    .expect("a refresh")
+                    .await
+                    .unwrap_or_else(|error| Err(error.into())),
+            );
+        }
+        terminal.draw(|frame| draw(frame, &mut app))?;
+        if event::poll(POLL)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            match app.handle(key.code) {
+                Action::Quit => {
+                    if let Some(refreshing) = refreshing {
+                        refreshing.abort();
+                    }
+                    return Ok(());
+                }
+                Action::Refresh if refreshing.is_none() => {
+                    app.status = "Refreshing...".to_string();
+                    refreshing = Some(refresh(options, github_api_token, username));
+                }
+                Action::Refresh => {}
+                Action::Open(url) => {
+                    if let Err(error) = open::that_detached(&url) {
+                        app.status = format!("Can't open {url}: {error}");
+                    }
+                }
+                Action::None => {}
+            }
+        }
+    }
+}
+
+/// Fetch the pull requests and the reviews on a new task. Nothing is printed on stderr, over the screen.
+fn refresh(
+    options: &PrArgs,
+    github_api_token: &str,
+    username: &str,
+) -> JoinHandle<Result<(Vec<ScoredPr>, Vec<Review>)>> {
+    let options = PrArgs {
+        debug: false,
+        ..options.clone()
+    };
+    let github_api_token = github_api_token.to_string();
+    let username = username.to_string();
+    tokio::spawn(async move { fetch(&options, &github_api_token, &username).await })
+}
+
+async fn fetch(
+    options: &PrArgs,
+    github_api_token: &str,
+    username: &str,
+) -> Result<(Vec<ScoredPr>, Vec<Review>)> {
+    let (fetched, reviews) = futures::join!(
+        fetch_scored_prs(github_api_token, username, options, None, false),
+        followup::followup(github_api_token, username, options.debug)
+    );
+    Ok((
+        diversity::spread(sorted_ranked_prs(fetched?.prs), options),
+        reviews?,
+    ))
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, body, status, keys] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [list, detail] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(body);
+
+    frame.render_widget(header_line(app), header);
+    // The list scrolls the state, which can't be borrowed with the pull requests
+    let mut state = app.state().clone();
+    match app.view {
+        View::Prs => {
+            let visible = app.visible_prs();
+            let items: Vec<ListItem> = visible
+                .iter()
+                .map(|(rank, spr)| ListItem::new(pr_line(*rank, spr)))
+                .collect();
+            let lines = state
+                .selected()
+                .and_then(|i| visible.get(i))
+                .map(|(_, spr)| pr_detail(spr))
+                .unwrap_or_default();
+            frame.render_stateful_widget(list_widget(items, "Pull requests"), list, &mut state);
+            frame.render_widget(detail_widget(lines), detail);
+        }
+        View::Followup => {
+            let visible = app.visible_reviews();
+            let items: Vec<ListItem> = visible
+                .iter()
+                .map(|review| ListItem::new(format!("{}  {}", review.state, review.pr_title)))
+                .collect();
+            let lines = state
+                .selected()
+                .and_then(|i| visible.get(i))
+                .map(|review| review_detail(review))
+                .unwrap_or_default();
+            frame.render_stateful_widget(list_widget(items, "Reviews"), list, &mut state);
+            frame.render_widget(detail_widget(lines), detail);
+        }
+    }
+    *app.state() = state;
+    frame.render_widget(Paragraph::new(app.status.as_str()), status);
+    frame.render_widget(Paragraph::new(KEYS).dark_gray(), keys);
+}
+
+fn header_line(app: &App) -> Line<'_> {
+    let tab = |view: View, text: String| {
+        if app.view == view {
+            Span::styled(
+                text,
+                Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            )
+        } else {
+            Span::raw(text)
+        }
+    };
+    let mut spans = vec![
+        tab(
+            View::Prs,
+            format!(" Pull requests ({}) ", app.visible_prs().len()),
+        ),
+        Span::raw(" "),
+        tab(
+            View::Followup,
+            format!(" Followup ({}) ", app.visible_reviews().len()),
+        ),
+    ];
+    for name in app.toggles.names() {
+        spans.push(Span::raw(format!("  [{name}]")).cyan());
+    }
+    if !app.snoozed.is_empty() {
+        spans.push(Span::raw(format!("  {} snoozed", app.snoozed.len())).dark_gray());
+    }
+    Line::from(spans)
+}
+
+fn list_widget<'a>(items: Vec<ListItem<'a>>, title: &'a str) -> List<'a> {
+    List::new(items)
+        .block(Block::new().borders(Borders::ALL).title(title))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+fn detail_widget(lines: Vec<Line<'_>>) -> Paragraph<'_> {
+    Paragraph::new(lines)
+        .block(Block::new().borders(Borders::ALL).title("Details"))
+        .wrap(Wrap { trim: false })
+}
+
+fn pr_line(rank: usize, spr: &ScoredPr) -> Line<'_> {
+    Line::from(vec![
+        Span::raw(format!("{rank:>3} {:>7.1} ", spr.score.total())),
+        Span::styled(
+            format!("{:<4} ", tests_result_label(&spr.pr.tests_result)),
+            ci_style(&spr.pr.tests_result),
+        ),
+        Span::raw(spr.pr.title.as_str()),
+    ])
+}
+
+fn pr_detail(spr: &ScoredPr) -> Vec<Line<'_>> {
+    let pr = &spr.pr;
+    let mut ci = vec![
+        Span::raw("CI: "),
+        Span::styled(
+            tests_result_label(&pr.tests_result),
+            ci_style(&pr.tests_result),
+        ),
+    ];
+    if !pr.failed_checks.is_empty() {
+        ci.push(Span::raw(format!(" ({})", pr.failed_checks.join(", "))));
+    }
+    ci.push(Span::raw("  All checks: "));
+    ci.push(Span::styled(
+        tests_result_label(&pr.all_tests_result),
+        ci_style(&pr.all_tests_result),
+    ));
+    let mut lines = vec![
+        Line::from(pr.title.as_str()).bold(),
+        Line::from(pr.url.as_str()).blue().underlined(),
+        Line::from(format!("{} by {}", pr.repo, pr.author)),
+        Line::from(
+            pr.labels
+                .0
+                .iter()
+                .flat_map(|label| [label_span(label), Span::raw(" ")])
+                .collect::<Vec<Span>>(),
+        ),
+        Line::default(),
+        Line::from(format!("Age: {}", show_duration(pr.last_commit_age_min))),
+        Line::from(ci),
+        Line::from(format!("Open conversations: {}", pr.open_conversations)),
+        Line::from(format!(
+            "Approvals: {}/{}  Reviewers: {}",
+            pr.num_approvals, pr.required_approvals, pr.num_reviewers
+        )),
+        Line::from(format!(
+            "Diff: +{} -{} in {} files",
+            pr.additions, pr.deletions, pr.changed_files
+        )),
+        Line::from(format!(
+            "Merge: {}  On main: {}  Blame: {}",
+            merge_state_label(&pr.merge_state),
+            show_bool(pr.based_on_main_branch),
+            show_bool(pr.blame)
+        )),
+        Line::from(format!(
+            "Requested: {}  Code owner: {}  Team: {}",
+            show_bool(pr.requested),
+            show_bool(pr.codeowner),
+            pr.team.as_deref().unwrap_or("-")
+        )),
+        Line::default(),
+        Line::from(format!("Score: {:.1}", spr.score.total())).bold(),
+    ];
+    lines.extend(
+        spr.score
+            .factors()
+            .into_iter()
+            .map(|(name, value)| Line::from(format!("  {name:<20}{value:>8.1}"))),
+    );
+    lines.push(Line::default());
+    lines.push(Line::from("Files").bold());
+    lines.extend(
+        pr.files
+            .0
+            .iter()
+            .map(|file| Line::from(format!("  {file}"))),
+    );
+    lines
+}
+
+fn review_detail(review: &Review) -> Vec<Line<'_>> {
+    vec![
+        Line::from(review.pr_title.as_str()).bold(),
+        Line::from(review.url.as_str()).blue().underlined(),
+        Line::default(),
+        Line::from(format!("State: {}", review.state)),
+    ]
+}
+
+/// The label with its GitHub color as background
+fn label_span(label: &Label) -> Span<'_> {
+    let (r, g, b) = label.rgb().unwrap_or((237, 237, 237));
+    let fg = if label.is_light() {
+        Color::Black
+    } else {
+        Color::White
+    };
+    Span::styled(
+        format!(" {} ", label.name),
+        Style::new().bg(Color::Rgb(r, g, b)).fg(fg),
+    )
+}
+
+fn ci_style(tests_result: &TestsState) -> Style {
+    Style::new().fg(match tests_result {
+        TestsState::Success => Color::Green,
+        TestsState::Pending => Color::Yellow,
+        TestsState::Failure => Color::Red,
+        TestsState::Missing => Color::Magenta,
+        TestsState::None => Color::DarkGray,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::*;
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn make_scored_pr(
+        title: &str,
+        tests_result: TestsState,
+        requested: bool,
+        codeowner: bool,
+    ) -> ScoredPr {
+        test_scored_pr(Pr {
+            title: title.to_string(),
+            url: format!("https://github.com/frisoft/ateam/pull/{title}"),
+            last_commit_age_min: Some(90),
+            tests_result: tests_result.clone(),
+            all_tests_result: tests_result,
+            open_conversations: 2,
+            num_reviewers: 1,
+            additions: 10,
+            deletions: 2,
+            based_on_main_branch: true,
+            files: Files(vec!["src/lib.rs".to_string()]),
+            labels: Labels(vec![Label {
+                name: "bug".to_string(),
+                color: "d73a4a".to_string(),
+            }]),
+            requested,
+            codeowner,
+            ..test_pr()
+        })
+    }
+
+    fn app() -> App {
+        let mut app = App::default();
+        app.load(Ok((
+            vec![
+                make_scored_pr("one", TestsState::Failure, true, false),
+                make_scored_pr("two", TestsState::Success, false, true),
+                make_scored_pr("three", TestsState::Success, true, true),
+            ],
+            vec![Review {
+                state: ReviewState::Dismissed,
+                url: "https://github.com/frisoft/ateam/pull/9".to_string(),
+                pr_title: "Old review".to_string(),
+            }],
+        )));
+        app
+    }
+
+    fn titles(app: &App) -> Vec<(usize, &str)> {
+        app.visible_prs()
+            .into_iter()
+            .map(|(rank, spr)| (rank, spr.pr.title.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_toggles() {
+        let mut app = app();
+        app.handle(KeyCode::Char('1'));
+        assert_eq!(titles(&app), vec![(1, "one"), (3, "three")]);
+        app.handle(KeyCode::Char('3'));
+        assert_eq!(titles(&app), vec![(3, "three")]);
+        app.handle(KeyCode::Char('1'));
+        app.handle(KeyCode::Char('2'));
+        assert_eq!(titles(&app), vec![(2, "two"), (3, "three")]);
+        assert_eq!(app.toggles.names(), vec!["code owner", "no failing CI"]);
+    }
+
+    #[test]
+    fn test_snooze() {
+        let mut app = app();
+        app.handle(KeyCode::End);
+        app.handle(KeyCode::Char('s'));
+        assert_eq!(titles(&app), vec![(1, "one"), (2, "two")]);
+        // The selection moves to the new last pull request
+        assert_eq!(app.prs_state.selected(), Some(1));
+        app.handle(KeyCode::Char('S'));
+        assert_eq!(titles(&app).len(), 3);
+    }
+
+    #[test]
+    fn test_move_and_open() {
+        let mut app = app();
+        app.handle(KeyCode::Up);
+        app.handle(KeyCode::Down);
+        app.handle(KeyCode::Down);
+        app.handle(KeyCode::Down);
+        assert_eq!(
+            app.handle(KeyCode::Enter),
+            Action::Open("https://github.com/frisoft/ateam/pull/three".to_string())
+        );
+        app.handle(KeyCode::Tab);
+        assert_eq!(
+            app.handle(KeyCode::Char('o')),
+            Action::Open("https://github.com/frisoft/ateam/pull/9".to_string())
+        );
+        assert_eq!(app.handle(KeyCode::Char('r')), Action::Refresh);
+        assert_eq!(app.handle(KeyCode::Char('q')), Action::Quit);
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut app = App::default();
+        app.clamp();
+        assert_eq!(app.handle(KeyCode::Down), Action::None);
+        assert_eq!(app.handle(KeyCode::Enter), Action::None);
+    }
+
+    #[test]
+    fn test_load_error_keeps_prs() {
+        let mut app = app();
+        app.load(Err(anyhow::anyhow!("rate limited")));
+        assert_eq!(app.status, "Error: rate limited");
+        assert_eq!(titles(&app).len(), 3);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut app = app();
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| draw(frame, &mut app)).unwrap();
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("Pull requests (3)"));
+        assert!(screen.contains("Fail one"));
+        assert!(screen.contains("Open conversations: 2"));
+        assert!(screen.contains("Code owner"));
+        assert!(screen.contains("src/lib.rs"));
+
+        app.handle(KeyCode::Tab);
+        terminal.draw(|frame| draw(frame, &mut app)).unwrap();
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("Dismissed  Old review"));
+        assert!(screen.contains("State: Dismissed"));
+    }
+}
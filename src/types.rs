@@ -6,20 +6,84 @@ use serde::Serialize;
 pub struct Pr {
     pub title: String,
     pub url: String,
+    pub repo: String,
+    pub author: String,
+    pub bot: bool,
+    pub created_at: Option<DateTime<Utc>>,
     pub last_commit_pushed_date: Option<DateTime<Utc>>,
     pub last_commit_age_min: Option<i64>,
     pub tests_result: TestsState,
+    pub all_tests_result: TestsState,
+    pub failed_checks: Vec<String>,
     pub open_conversations: i64,
     pub num_approvals: i64,
     pub num_reviewers: i64,
+    pub required_approvals: u8,
     pub additions: i64,
     pub deletions: i64,
+    pub changed_files: i64,
     pub based_on_main_branch: bool,
+    pub merge_state: MergeState,
     pub files: Files,
     pub blame: bool,
     pub labels: Labels,
     pub requested: bool,
     pub codeowner: bool,
+    pub team: Option<String>,
+    pub reviewed_by_me: bool,
+}
+
+impl Pr {
+    /// The age of the last commit in minutes, or of the pull request when the date of the
+    /// last commit is unknown
+    pub fn age_min(&self) -> Option<i64> {
+        self.last_commit_age_min.or_else(|| {
+            self.created_at
+                .map(|created_at| (Utc::now() - created_at).num_minutes())
+        })
+    }
+}
+
+/// A pull request for the tests, with the fields of a small change ready to be reviewed.
+/// The tests change the fields they check with the struct update syntax.
+#[cfg(test)]
+pub fn test_pr() -> Pr {
+    Pr {
+        title: "Some important changes".to_string(),
+        url: "https://github.com/frisoft/ateam/pull/1".to_string(),
+        repo: "frisoft/ateam".to_string(),
+        author: "alice".to_string(),
+        bot: false,
+        created_at: None,
+        last_commit_pushed_date: None,
+        last_commit_age_min: None,
+        tests_result: TestsState::Success,
+        all_tests_result: TestsState::Success,
+        failed_checks: vec![],
+        open_conversations: 0,
+        num_approvals: 0,
+        num_reviewers: 0,
+        required_approvals: 1,
+        additions: 1,
+        deletions: 0,
+        changed_files: 1,
+        based_on_main_branch: false,
+        merge_state: MergeState::Clean,
+        files: Files(vec![]),
+        blame: false,
+        labels: Labels(vec![]),
+        requested: false,
+        codeowner: false,
+        team: None,
+        reviewed_by_me: false,
+    }
+}
+
+/// A scored pull request for the tests, with 1 required approval
+#[cfg(test)]
+pub fn test_scored_pr(pr: Pr) -> ScoredPr {
+    let score = Score::from_pr(1, &pr);
+    ScoredPr { pr, score }
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -27,9 +91,21 @@ pub enum TestsState {
     Pending,
     Success,
     Failure,
+    Missing,
     None,
 }
 
+#[derive(Serialize, Debug, Clone)]
+pub enum MergeState {
+    Clean,
+    Behind,
+    Blocked,
+    Unstable,
+    HasHooks,
+    Conflicting,
+    Unknown,
+}
+
 #[derive(Serialize)]
 pub enum ReviewRequested {
     RequestedAsCodeOwner,
@@ -65,6 +141,7 @@ pub struct Score {
     pub additions: f64,
     pub deletions: f64,
     pub based_on_main_branch: f64,
+    pub merge_state: f64,
     pub blame: f64,
     pub requested: f64,
     pub codeowner: f64,
@@ -73,10 +150,18 @@ pub struct Score {
 impl Score {
     pub fn from_pr(required_approvals: u8, pr: &Pr) -> Score {
         let tests_result_i = match pr.tests_result {
-            TestsState::Pending => 1,
+            TestsState::Pending | TestsState::Missing => 1,
             TestsState::Failure => 2,
             TestsState::Success | TestsState::None => 0, // a repo without CI is treated as successful
         };
+        let merge_state = match pr.merge_state {
+            MergeState::Behind | MergeState::Blocked => -50.0,
+            MergeState::Conflicting => -200.0,
+            MergeState::Clean
+            | MergeState::Unstable
+            | MergeState::HasHooks
+            | MergeState::Unknown => 0.0,
+        };
         #[allow(clippy::cast_precision_loss, clippy::cast_lossless)]
         Score {
             age: pr.last_commit_age_min.unwrap_or(0) as f64 / 60.0 * 2.0,
@@ -87,6 +172,7 @@ impl Score {
             additions: pr.additions as f64 * -0.5,
             deletions: pr.deletions as f64 * -0.1,
             based_on_main_branch: u8::from(pr.based_on_main_branch) as f64 * 200.0,
+            merge_state,
             blame: u8::from(pr.blame) as f64 * 400.0,
             requested: u8::from(pr.requested) as f64 * 800.0,
             codeowner: u8::from(pr.codeowner) as f64 * 400.0,
@@ -102,10 +188,29 @@ impl Score {
             + self.additions
             + self.deletions
             + self.based_on_main_branch
+            + self.merge_state
             + self.blame
             + self.requested
             + self.codeowner
     }
+
+    /// The name and the value of every factor of the total
+    pub fn factors(&self) -> [(&'static str, f64); 12] {
+        [
+            ("Age", self.age),
+            ("Tests", self.tests_result),
+            ("Open conversations", self.open_conversations),
+            ("Approvals", self.num_approvals),
+            ("Reviewers", self.num_reviewers),
+            ("Additions", self.additions),
+            ("Deletions", self.deletions),
+            ("On main", self.based_on_main_branch),
+            ("Merge state", self.merge_state),
+            ("Blame", self.blame),
+            ("Requested", self.requested),
+            ("Code owner", self.codeowner),
+        ]
+    }
 }
 
 impl std::fmt::Display for Pr {
@@ -128,12 +233,43 @@ impl std::fmt::Display for Labels {
     }
 }
 
+impl Label {
+    /// The color of the label, if it is a valid RRGGBB hex color
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        let color = self.color.trim_start_matches('#');
+        if color.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(color.get(i..i + 2)?, 16).ok();
+        Some((channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// True if the label needs a dark text, by the perceived brightness of the W3C accessibility guidelines
+    pub fn is_light(&self) -> bool {
+        self.rgb().is_none_or(|(r, g, b)| {
+            (u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) / 1000 > 140
+        })
+    }
+}
+
 impl std::fmt::Display for Label {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "({})", self.name,)
     }
 }
 
+/// What was searched, for the headers of the reports
+#[derive(Debug, Serialize, Clone)]
+pub struct Summary {
+    pub queries: Vec<String>,
+    pub user: String,
+    pub generated_at: DateTime<Utc>,
+    /// Fetched from GitHub, before the filters
+    pub fetched: usize,
+    /// Kept by the filters
+    pub total: usize,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct Review {
     pub state: ReviewState,
@@ -164,22 +300,13 @@ mod tests {
     #[test]
     fn pr_fmt_method() {
         let pr = Pr {
-            title: "Some important changes".to_string(),
-            url: "https://github.com/frisoft/ateam/pull/1".to_string(),
-            last_commit_pushed_date: None,
-            last_commit_age_min: None,
-            tests_result: TestsState::Success,
-            open_conversations: 0,
             num_approvals: 1,
             num_reviewers: 2,
             additions: 1000,
             deletions: 999,
             based_on_main_branch: true,
-            files: Files(vec![]),
-            blame: false,
-            labels: Labels(vec![]),
             requested: true,
-            codeowner: false,
+            ..test_pr()
         };
 
         assert_eq!(
@@ -187,4 +314,21 @@ mod tests {
             "https://github.com/frisoft/ateam/pull/1 - Some important changes ",
         );
     }
+
+    #[test]
+    fn score_merge_state() {
+        let mut pr = Pr {
+            additions: 0,
+            based_on_main_branch: true,
+            ..test_pr()
+        };
+        let clean = Score::from_pr(1, &pr).total();
+        pr.merge_state = MergeState::Behind;
+        let behind = Score::from_pr(1, &pr).total();
+        pr.merge_state = MergeState::Conflicting;
+        let conflicting = Score::from_pr(1, &pr).total();
+
+        assert!(clean > behind);
+        assert!(behind > conflicting);
+    }
 }